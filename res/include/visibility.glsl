@@ -0,0 +1,10 @@
+// Must match `fog::Visibility`
+const uint UNSEEN = 0u;
+const uint REMEMBERED = 1u;
+
+// Remembered tiles are drawn washed out and dark so they're easy to tell apart
+// from what's actually in view.
+vec4 remembered(vec4 color) {
+    float luma = dot(color.rgb, vec3(0.299, 0.587, 0.114));
+    return vec4(mix(color.rgb, vec3(luma), 0.7) * 0.4, color.a);
+}
@@ -0,0 +1,186 @@
+use gl_api::layout::VertexAttribute;
+use gl_api::shader::preprocess::{Defines, VariantCache};
+use gl_api::shader::program::Program;
+use gl_api::shader::PipelineError;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Files that changed since last frame, for anything that needs to reload
+/// itself. Filled in by whoever owns the `AssetWatcher`.
+#[derive(Clone, Debug, Default)]
+pub struct ChangedAssets(pub Vec<PathBuf>);
+
+/// Notices when files in a directory get changed, by checking their
+/// modification times every so often.
+pub struct AssetWatcher {
+    root: PathBuf,
+    modified: HashMap<PathBuf, SystemTime>,
+    interval: Duration,
+    last_poll: Instant,
+}
+
+impl AssetWatcher {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        let root = root.into();
+        let mut modified = HashMap::new();
+        scan(&root, &mut modified);
+        AssetWatcher { root, modified, interval: Duration::from_millis(500), last_poll: Instant::now() }
+    }
+
+    /// How long to wait between looking at the files.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Every file that was changed or added since the last time we looked.
+    /// Doesn't actually look if it hasn't been long enough.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.interval {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let mut modified = HashMap::new();
+        scan(&self.root, &mut modified);
+        let changed = modified.iter()
+            .filter(|&(path, time)| self.modified.get(path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        self.modified = modified;
+        changed
+    }
+}
+
+// Records the modification time of everything under `dir`. Anything that can't
+// be read is skipped, since it might be halfway through being saved.
+fn scan(dir: &Path, modified: &mut HashMap<PathBuf, SystemTime>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        match entry.metadata() {
+            Ok(ref metadata) if metadata.is_dir() => scan(&path, modified),
+            Ok(metadata) => if let Ok(time) = metadata.modified() {
+                modified.insert(path, time);
+            },
+            Err(_) => (),
+        }
+    }
+}
+
+/// Prints out why something couldn't be loaded.
+pub fn report(what: &str, err: &PipelineError) {
+    eprintln!("couldn't reload {}: {}", what, err);
+}
+
+/// A program that can build itself again when any of its shaders change. It
+/// can also be built with different `#define`s, and keeps every variant that's
+/// been built around so switching back and forth doesn't compile anything.
+pub struct HotProgram<I, E> {
+    name: String,
+    // Which variant `get` gives back.
+    defines: Defines,
+    variants: VariantCache<Program<I, E>>,
+    build: Box<Fn(&Defines) -> Result<Program<I, E>, PipelineError>>,
+}
+
+impl<I: VertexAttribute, E> HotProgram<I, E> {
+    /// `name` is used for error messages, and as the program's debug label.
+    /// `build` gets the defines for whichever variant it's building, and the
+    /// first one has none.
+    pub fn new<F>(name: &str, build: F) -> Result<Self, PipelineError>
+    where F: Fn(&Defines) -> Result<Program<I, E>, PipelineError> + 'static {
+        let mut program = HotProgram {
+            name: name.into(),
+            defines: Defines::new(),
+            variants: VariantCache::new(),
+            build: Box::new(build),
+        };
+        program.select(Defines::new())?;
+        Ok(program)
+    }
+
+    pub fn get(&self) -> &Program<I, E> {
+        // UNWRAP: variants are built before they're selected
+        self.variants.get(&self.defines).unwrap()
+    }
+
+    pub fn get_mut(&mut self) -> &mut Program<I, E> {
+        // UNWRAP: variants are built before they're selected
+        self.variants.get_mut(&self.defines).unwrap()
+    }
+
+    /// Switches to the variant built with `defines`, building it first if it
+    /// hasn't been already. Every variant has its own environment, so anything
+    /// set in it has to be set again afterwards.
+    pub fn select(&mut self, defines: Defines) -> Result<(), PipelineError> {
+        {
+            let HotProgram { ref name, ref mut variants, ref build, .. } = *self;
+            variants.get_or_build(&defines, |defines| -> Result<_, PipelineError> {
+                let program = build(defines)?;
+                program.set_label(name);
+                Ok(program)
+            })?;
+        }
+        self.defines = defines;
+        Ok(())
+    }
+
+    /// Rebuilds the program if any of `changed` went into it. If the new one
+    /// doesn't build, the old one gets kept and the reason is printed out.
+    /// Returns whether the program got replaced, in which case anything set in
+    /// its environment has to be set again.
+    pub fn reload(&mut self, changed: &[PathBuf]) -> bool {
+        if !self.get().files().iter().any(|file| changed.contains(file)) {
+            return false;
+        }
+
+        match (self.build)(&self.defines) {
+            Ok(program) => {
+                program.set_label(&self.name);
+                // The other variants are out of date now too, so they get
+                // built again whenever they're selected next.
+                self.variants.clear();
+                self.variants.insert(self.defines.clone(), program);
+                true
+            }
+            Err(err) => {
+                report(&self.name, &err);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn notices_changes() {
+        let root = env::temp_dir().join("birblike-assets");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("post")).unwrap();
+        fs::write(root.join("world.glslf"), "old").unwrap();
+
+        let mut watcher = AssetWatcher::new(&root).with_interval(Duration::from_secs(0));
+        assert!(watcher.poll().is_empty());
+
+        // Give the modification time a chance to actually move.
+        ::std::thread::sleep(Duration::from_millis(20));
+        fs::write(root.join("world.glslf"), "new").unwrap();
+        fs::write(root.join("post/crt.glslf"), "added").unwrap();
+
+        let mut changed = watcher.poll();
+        changed.sort();
+        assert_eq!(changed, vec![root.join("post/crt.glslf"), root.join("world.glslf")]);
+        assert!(watcher.poll().is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+use cgmath::{Vector2, Vector4};
+use fov::Viewshed;
+use grid::GridX;
+use specs::prelude::*;
+use specs::shred::PanicHandler;
+use std::collections::HashSet;
+
+/// How much the observers know about a cell. The discriminants are what the
+/// world shader gets to see, so keep them in sync with `visibility.glsl`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[repr(u32)]
+pub enum Visibility {
+    /// Never been in view.
+    Unseen = 0,
+    /// Was seen at some point, but isn't in view right now.
+    Remembered = 1,
+    /// In view this turn.
+    Visible = 2,
+}
+
+impl Visibility {
+    /// Applies the fog to a color the same way `visibility.glsl` does, for
+    /// renderers that don't go through the shader.
+    pub fn shade(&self, color: Vector4<f32>) -> Vector4<f32> {
+        match *self {
+            Visibility::Unseen => Vector4::new(0.0, 0.0, 0.0, 1.0),
+            Visibility::Remembered => {
+                let luma = color.x * 0.299 + color.y * 0.587 + color.z * 0.114;
+                let gray = |c: f32| (c + (luma - c) * 0.7) * 0.4;
+                Vector4::new(gray(color.x), gray(color.y), gray(color.z), color.w)
+            }
+            Visibility::Visible => color,
+        }
+    }
+}
+
+/// The entity the map gets drawn for, which is normally just the player. The
+/// fog shows what it can see and what it remembers.
+#[derive(Default)]
+pub struct Observer;
+
+impl Component for Observer {
+    type Storage = NullStorage<Self>;
+}
+
+/// Every cell an entity with a `Viewshed` has ever seen.
+#[derive(Clone, Debug, Default)]
+pub struct Revealed {
+    cells: HashSet<Vector2<usize>>,
+}
+
+impl Revealed {
+    pub fn has_seen(&self, pos: Vector2<usize>) -> bool {
+        self.cells.contains(&pos)
+    }
+}
+
+impl Component for Revealed {
+    type Storage = VecStorage<Self>;
+}
+
+/// What the `Observer` knows about each cell of the map, ready to be drawn.
+#[derive(Debug)]
+pub struct FogOfWar {
+    cells: GridX<Visibility>,
+    changed: bool,
+}
+
+impl FogOfWar {
+    pub fn new(width: usize, height: usize) -> Self {
+        FogOfWar {
+            cells: GridX::from_iter(vec![Visibility::Unseen; width * height], width, height),
+            changed: true,
+        }
+    }
+
+    pub fn get(&self, pos: Vector2<usize>) -> Visibility {
+        self.cells[pos]
+    }
+
+    /// Whether any cell changed visibility during the last update.
+    pub fn changed(&self) -> bool {
+        self.changed
+    }
+}
+
+/// Adds whatever every viewer can see to what it's `Revealed`, then rebuilds
+/// the fog from the `Observer`'s point of view.
+pub struct FogOfWarSystem;
+
+impl<'a> System<'a> for FogOfWarSystem {
+    type SystemData = (
+        Write<'a, FogOfWar, PanicHandler>,
+        ReadStorage<'a, Observer>,
+        ReadStorage<'a, Viewshed>,
+        WriteStorage<'a, Revealed>,
+    );
+
+    fn run(&mut self, (mut fog, observers, viewsheds, mut revealed): Self::SystemData) {
+        let (width, height) = fog.cells.dimensions();
+        let in_bounds = |pos: &&Vector2<usize>| pos.x < width && pos.y < height;
+
+        for (viewshed, revealed) in (&viewsheds, &mut revealed).join() {
+            revealed.cells.extend(viewshed.visible.iter().filter(in_bounds));
+        }
+
+        let mut next = GridX::from_iter(vec![Visibility::Unseen; width * height], width, height);
+        for (_, viewshed, revealed) in (&observers, &viewsheds, &revealed).join() {
+            for y in 0..height {
+                for x in 0..width {
+                    let pos = Vector2::new(x, y);
+                    if viewshed.can_see(pos) {
+                        next[pos] = Visibility::Visible;
+                    } else if revealed.has_seen(pos) && next[pos] == Visibility::Unseen {
+                        next[pos] = Visibility::Remembered;
+                    }
+                }
+            }
+        }
+
+        fog.changed = next != fog.cells;
+        fog.cells = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Observer>();
+        world.register::<Viewshed>();
+        world.register::<Revealed>();
+        world.add_resource(FogOfWar::new(3, 1));
+        world
+    }
+
+    fn viewshed(cells: &[(usize, usize)]) -> Viewshed {
+        let mut viewshed = Viewshed::new(8);
+        viewshed.visible.extend(cells.iter().map(|&(x, y)| Vector2::new(x, y)));
+        viewshed
+    }
+
+    fn fog(world: &World) -> Vec<Visibility> {
+        let fog = world.read_resource::<FogOfWar>();
+        (0..3).map(|x| fog.get(Vector2::new(x, 0))).collect()
+    }
+
+    #[test]
+    fn cells_out_of_view_are_remembered() {
+        use self::Visibility::*;
+
+        let mut world = world();
+        let observer = world.create_entity()
+            .with(viewshed(&[(0, 0), (1, 0)]))
+            .with(Revealed::default())
+            .with(Observer)
+            .build();
+        FogOfWarSystem.run_now(&world.res);
+        assert_eq!(fog(&world), vec![Visible, Visible, Unseen]);
+        assert!(world.read_resource::<FogOfWar>().changed());
+
+        *world.write_storage::<Viewshed>().get_mut(observer).unwrap() = viewshed(&[(1, 0)]);
+        FogOfWarSystem.run_now(&world.res);
+        assert_eq!(fog(&world), vec![Remembered, Visible, Unseen]);
+        assert!(world.read_resource::<FogOfWar>().changed());
+
+        // Nothing moved, so nothing changed
+        FogOfWarSystem.run_now(&world.res);
+        assert_eq!(fog(&world), vec![Remembered, Visible, Unseen]);
+        assert!(!world.read_resource::<FogOfWar>().changed());
+    }
+
+    #[test]
+    fn cells_off_the_map_are_ignored() {
+        let mut world = world();
+        let observer = world.create_entity()
+            .with(viewshed(&[(2, 0), (3, 0), (0, 5)]))
+            .with(Revealed::default())
+            .with(Observer)
+            .build();
+        FogOfWarSystem.run_now(&world.res);
+
+        assert_eq!(fog(&world), vec![Visibility::Unseen, Visibility::Unseen, Visibility::Visible]);
+        let revealed = world.read_storage::<Revealed>();
+        let revealed = revealed.get(observer).unwrap();
+        assert!(revealed.has_seen(Vector2::new(2, 0)));
+        assert!(!revealed.has_seen(Vector2::new(3, 0)));
+        assert!(!revealed.has_seen(Vector2::new(0, 5)));
+    }
+
+    #[test]
+    fn viewers_remember_separately() {
+        let mut world = world();
+        world.create_entity()
+            .with(viewshed(&[(0, 0)]))
+            .with(Revealed::default())
+            .with(Observer)
+            .build();
+        let other = world.create_entity()
+            .with(viewshed(&[(2, 0)]))
+            .with(Revealed::default())
+            .build();
+        FogOfWarSystem.run_now(&world.res);
+
+        // Only the observer's view makes it into the fog, but everyone keeps
+        // track of what they've seen
+        assert_eq!(fog(&world), vec![Visibility::Visible, Visibility::Unseen, Visibility::Unseen]);
+        let revealed = world.read_storage::<Revealed>();
+        assert!(revealed.get(other).unwrap().has_seen(Vector2::new(2, 0)));
+        assert!(!revealed.get(other).unwrap().has_seen(Vector2::new(0, 0)));
+    }
+}
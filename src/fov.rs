@@ -0,0 +1,242 @@
+use cgmath::Vector2;
+use specs::prelude::*;
+use specs::shred::PanicHandler;
+use std::collections::HashSet;
+use terrain::TerrainKind;
+use {TileGrid, TilePos};
+
+// Transforms from octant-local (column, row) offsets into grid offsets, as
+// (xx, xy, yx, yy). Together these cover all eight octants around the origin.
+const OCTANTS: [(isize, isize, isize, isize); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+/// Computes the set of cells visible from `origin` within `radius` cells using
+/// recursive shadowcasting. Opaque cells block sight but are themselves
+/// visible, so walls around a room show up. Anything outside of `dims` is
+/// treated as opaque.
+pub fn field_of_view<F>(origin: Vector2<usize>, radius: usize, dims: (usize, usize), is_opaque: F) -> HashSet<Vector2<usize>>
+where
+    F: Fn(Vector2<usize>) -> bool,
+{
+    let mut visible = HashSet::new();
+    visible.insert(origin);
+    for octant in &OCTANTS {
+        cast_light(&mut visible, &is_opaque, origin, radius, dims, *octant, 1, 1.0, 0.0);
+    }
+    visible
+}
+
+// See http://www.roguebasin.com/index.php?title=FOV_using_recursive_shadowcasting
+// for a description of the algorithm. Slopes go from `start` (the outer edge of
+// the octant) down to `end`; every time we hit an opaque cell, the part of the
+// row before it is scanned recursively and the rest of the scan is narrowed.
+fn cast_light<F>(
+    visible: &mut HashSet<Vector2<usize>>,
+    is_opaque: &F,
+    origin: Vector2<usize>,
+    radius: usize,
+    (width, height): (usize, usize),
+    (xx, xy, yx, yy): (isize, isize, isize, isize),
+    row: usize,
+    mut start: f32,
+    end: f32,
+) where
+    F: Fn(Vector2<usize>) -> bool,
+{
+    if start < end {
+        return;
+    }
+
+    let radius_sq = (radius * radius) as isize;
+    let mut next_start = start;
+    for distance in row..radius + 1 {
+        let dy = -(distance as isize);
+        let mut blocked = false;
+
+        for dx in dy..1 {
+            let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+            let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+            if start < right_slope {
+                continue;
+            } else if end > left_slope {
+                break;
+            }
+
+            let (x, y) = (origin.x as isize + dx * xx + dy * xy, origin.y as isize + dx * yx + dy * yy);
+            let opaque = if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height {
+                let pos = Vector2::new(x as usize, y as usize);
+                if dx * dx + dy * dy <= radius_sq {
+                    visible.insert(pos);
+                }
+                is_opaque(pos)
+            } else {
+                true
+            };
+
+            if blocked {
+                if opaque {
+                    next_start = right_slope;
+                } else {
+                    blocked = false;
+                    start = next_start;
+                }
+            } else if opaque && distance < radius {
+                blocked = true;
+                cast_light(visible, is_opaque, origin, radius, (width, height), (xx, xy, yx, yy), distance + 1, start, left_slope);
+                next_start = right_slope;
+            }
+        }
+
+        if blocked {
+            break;
+        }
+    }
+}
+
+/// The cells an entity with a `TilePos` can currently see.
+#[derive(Clone, Debug)]
+pub struct Viewshed {
+    pub range: usize,
+    pub visible: HashSet<Vector2<usize>>,
+    dirty: bool,
+}
+
+impl Viewshed {
+    pub fn new(range: usize) -> Self {
+        Viewshed {
+            range,
+            visible: HashSet::new(),
+            dirty: true,
+        }
+    }
+
+    pub fn can_see(&self, pos: Vector2<usize>) -> bool {
+        self.visible.contains(&pos)
+    }
+}
+
+impl Component for Viewshed {
+    type Storage = VecStorage<Self>;
+}
+
+/// Recomputes viewsheds for entities that moved, and for everyone if the
+/// terrain changed.
+pub struct ViewshedSystem {
+    moved_id: ReaderId<ModifiedFlag>,
+    terrain_inserted_id: ReaderId<InsertedFlag>,
+    terrain_modified_id: ReaderId<ModifiedFlag>,
+    moved: BitSet,
+}
+
+impl ViewshedSystem {
+    pub fn new(world: &mut World) -> Self {
+        let moved_id = world.write_storage::<TilePos>().track_modified();
+        let mut kinds = world.write_storage::<TerrainKind>();
+        ViewshedSystem {
+            moved_id,
+            terrain_inserted_id: kinds.track_inserted(),
+            terrain_modified_id: kinds.track_modified(),
+            moved: BitSet::new(),
+        }
+    }
+}
+
+impl<'a> System<'a> for ViewshedSystem {
+    type SystemData = (
+        Read<'a, TileGrid, PanicHandler>,
+        Entities<'a>,
+        ReadStorage<'a, TilePos>,
+        ReadStorage<'a, TerrainKind>,
+        WriteStorage<'a, Viewshed>,
+    );
+
+    fn run(&mut self, (grid, entities, positions, kinds, mut viewsheds): Self::SystemData) {
+        self.moved.clear();
+        self.moved.extend(positions.modified().read(&mut self.moved_id).map(|item| *item.as_ref()));
+
+        // Both channels need to be read every frame, even if we already know we
+        // have to recompute everything.
+        let terrain_inserted = kinds.inserted().read(&mut self.terrain_inserted_id).len() > 0;
+        let terrain_modified = kinds.modified().read(&mut self.terrain_modified_id).len() > 0;
+        let terrain_changed = terrain_inserted || terrain_modified;
+
+        for (entity, viewshed) in (&*entities, &mut viewsheds).join() {
+            viewshed.dirty |= terrain_changed || self.moved.contains(entity.id());
+        }
+
+        let dims = grid.dimensions();
+        let is_opaque = |pos| kinds.get(grid[pos]).map_or(true, TerrainKind::is_opaque);
+        for (pos, viewshed) in (&positions, &mut viewsheds).join() {
+            if viewshed.dirty {
+                viewshed.visible = field_of_view(**pos, viewshed.range, dims, &is_opaque);
+                viewshed.dirty = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grid::GridX;
+
+    fn grid_from_str(rows: &[&str]) -> GridX<bool> {
+        let (width, height) = (rows[0].len(), rows.len());
+        let cells = rows.iter().rev()
+            .flat_map(|row| row.chars())
+            .map(|ch| ch == '#')
+            .collect::<Vec<_>>();
+        GridX::from_iter(cells, width, height)
+    }
+
+    #[test]
+    fn open_room_is_fully_visible() {
+        let grid = grid_from_str(&[
+            "#######",
+            "#.....#",
+            "#.....#",
+            "#.....#",
+            "#######",
+        ]);
+        let visible = field_of_view(Vector2::new(3, 2), 10, grid.dimensions(), |pos| grid[pos]);
+        for y in 0..5 {
+            for x in 0..7 {
+                assert!(visible.contains(&Vector2::new(x, y)), "({}, {}) should be visible", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn walls_cast_shadows() {
+        let grid = grid_from_str(&[
+            ".......",
+            ".......",
+            "...#...",
+            ".......",
+        ]);
+        let visible = field_of_view(Vector2::new(3, 0), 10, grid.dimensions(), |pos| grid[pos]);
+        assert!(visible.contains(&Vector2::new(3, 1)));
+        assert!(!visible.contains(&Vector2::new(3, 2)));
+        assert!(!visible.contains(&Vector2::new(3, 3)));
+        assert!(visible.contains(&Vector2::new(0, 3)));
+        assert!(visible.contains(&Vector2::new(6, 3)));
+    }
+
+    #[test]
+    fn radius_limits_sight() {
+        let grid = grid_from_str(&[".........."; 10]);
+        let visible = field_of_view(Vector2::new(0, 0), 3, grid.dimensions(), |pos| grid[pos]);
+        assert!(visible.contains(&Vector2::new(3, 0)));
+        assert!(visible.contains(&Vector2::new(2, 2)));
+        assert!(!visible.contains(&Vector2::new(4, 0)));
+        assert!(!visible.contains(&Vector2::new(3, 3)));
+    }
+}
@@ -0,0 +1,472 @@
+use cgmath::{Matrix2, Matrix3, Matrix4, Vector2, Vector3, Vector4};
+use gl_api::buffer::{BufferMapError, BufferMapMut, IndexedBuffer, UsageType};
+use gl_api::buffer;
+use gl_api::error::GlResult;
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+
+/// Rounds `offset` up to the next multiple of `align`.
+pub fn align_to(offset: usize, align: usize) -> usize {
+    (offset + align - 1) / align * align
+}
+
+// Copies the bytes of `value` to the start of `out`.
+fn write_raw<T: Copy>(value: &T, out: &mut [u8]) {
+    assert!(out.len() >= mem::size_of::<T>());
+    unsafe {
+        ptr::copy_nonoverlapping(value as *const T as *const u8, out.as_mut_ptr(), mem::size_of::<T>());
+    }
+}
+
+/// Types that can be laid out in a `std140` interface block. The rules are in
+/// section 7.6.2.2 of the spec.
+pub trait Std140 {
+    /// The base alignment of the type.
+    fn std140_align() -> usize;
+    /// How many bytes the type takes up. The next member might start before
+    /// the end of the alignment, like a `float` right after a `vec3`.
+    fn std140_size() -> usize;
+    /// Writes the type into the start of `out`, which is at least
+    /// `std140_size` bytes long. Padding is left alone.
+    fn write_std140(&self, out: &mut [u8]);
+}
+
+/// Structs that make up a whole block, usually declared with `std140!`.
+pub trait Std140Block: Std140 {
+    /// The offset of each member, in declaration order.
+    fn std140_offsets() -> Vec<(&'static str, usize)>;
+}
+
+macro_rules! std140_raw {
+    ($($type:ty: $align:expr),* $(,)*) => {
+        $(impl Std140 for $type {
+            fn std140_align() -> usize { $align }
+            fn std140_size() -> usize { mem::size_of::<$type>() }
+            fn write_std140(&self, out: &mut [u8]) { write_raw(self, out) }
+        })*
+    };
+}
+
+std140_raw! {
+    f32: 4, i32: 4, u32: 4, f64: 8,
+    Vector2<f32>: 8, Vector2<i32>: 8, Vector2<u32>: 8, Vector2<f64>: 16,
+    Vector3<f32>: 16, Vector3<i32>: 16, Vector3<u32>: 16, Vector3<f64>: 32,
+    Vector4<f32>: 16, Vector4<i32>: 16, Vector4<u32>: 16, Vector4<f64>: 32,
+    // Columns are already vec4 sized, so these can be copied as is.
+    Matrix4<f32>: 16,
+}
+
+impl Std140 for bool {
+    fn std140_align() -> usize { 4 }
+    fn std140_size() -> usize { 4 }
+    fn write_std140(&self, out: &mut [u8]) { write_raw(&(*self as u32), out) }
+}
+
+// Matrices are laid out like arrays of their columns, so each column gets
+// padded out to a vec4.
+impl Std140 for Matrix2<f32> {
+    fn std140_align() -> usize { 16 }
+    fn std140_size() -> usize { 32 }
+    fn write_std140(&self, out: &mut [u8]) {
+        self.x.write_std140(&mut out[0..]);
+        self.y.write_std140(&mut out[16..]);
+    }
+}
+
+impl Std140 for Matrix3<f32> {
+    fn std140_align() -> usize { 16 }
+    fn std140_size() -> usize { 48 }
+    fn write_std140(&self, out: &mut [u8]) {
+        self.x.write_std140(&mut out[0..]);
+        self.y.write_std140(&mut out[16..]);
+        self.z.write_std140(&mut out[32..]);
+    }
+}
+
+macro_rules! std140_array {
+    ($($len:expr),*) => {
+        $(impl<T: Std140> Std140 for [T; $len] {
+            // Array elements are always aligned like a vec4.
+            fn std140_align() -> usize { align_to(T::std140_align(), 16) }
+            fn std140_size() -> usize { $len * align_to(T::std140_size(), Self::std140_align()) }
+            fn write_std140(&self, out: &mut [u8]) {
+                let stride = Self::std140_size() / $len;
+                for (i, item) in self.iter().enumerate() {
+                    item.write_std140(&mut out[i * stride..]);
+                }
+            }
+        })*
+    };
+}
+
+std140_array!(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 32, 64);
+
+/// Declares a struct that can be used as a `std140` uniform block, or as a
+/// member of one. Members are laid out in order, same as in GLSL.
+macro_rules! std140 {
+    (block $name:ident {
+        $($member:ident: $member_type:ty,)*
+    }) => {
+        #[derive(Copy, Clone, Debug)]
+        pub struct $name {
+            $(pub $member: $member_type),*
+        }
+
+        impl ::gl_api::block::Std140 for $name {
+            fn std140_align() -> usize {
+                // The largest alignment of any member, rounded up to a vec4.
+                let align = 16;
+                $(let align = align.max(<$member_type as ::gl_api::block::Std140>::std140_align());)*
+                align
+            }
+
+            fn std140_size() -> usize {
+                #[allow(unused_mut)]
+                let mut end = 0;
+                $(
+                    end = ::gl_api::block::align_to(end, <$member_type as ::gl_api::block::Std140>::std140_align());
+                    end += <$member_type as ::gl_api::block::Std140>::std140_size();
+                )*
+                // Structs get padded out to their alignment, so that whatever
+                // comes next starts on a boundary.
+                ::gl_api::block::align_to(end, Self::std140_align())
+            }
+
+            #[allow(unused_variables)]
+            fn write_std140(&self, out: &mut [u8]) {
+                let mut offsets = <Self as ::gl_api::block::Std140Block>::std140_offsets().into_iter();
+                $(
+                    // UNWRAP: there's one offset per member
+                    let (_, offset) = offsets.next().unwrap();
+                    ::gl_api::block::Std140::write_std140(&self.$member, &mut out[offset..]);
+                )*
+            }
+        }
+
+        impl ::gl_api::block::Std140Block for $name {
+            #[allow(unused_mut, unused_assignments)]
+            fn std140_offsets() -> Vec<(&'static str, usize)> {
+                let mut offsets = Vec::new();
+                let mut end = 0;
+                $(
+                    let offset = ::gl_api::block::align_to(end, <$member_type as ::gl_api::block::Std140>::std140_align());
+                    offsets.push((stringify!($member), offset));
+                    end = offset + <$member_type as ::gl_api::block::Std140>::std140_size();
+                )*
+                offsets
+            }
+        }
+    };
+}
+
+/// Types that can be laid out in a `std430` interface block, which is what
+/// shader storage blocks use. It's the same as `std140`, except that arrays
+/// and structs aren't padded out to a vec4.
+pub trait Std430 {
+    fn std430_align() -> usize;
+    fn std430_size() -> usize;
+    fn write_std430(&self, out: &mut [u8]);
+
+    /// The offset of each member of a struct, in declaration order. Empty for
+    /// anything that isn't a struct.
+    fn std430_offsets() -> Vec<(&'static str, usize)> {
+        Vec::new()
+    }
+
+    /// The distance between consecutive elements of an array of this type.
+    fn std430_stride() -> usize {
+        align_to(Self::std430_size(), Self::std430_align())
+    }
+}
+
+macro_rules! std430_raw {
+    ($($type:ty: $align:expr),* $(,)*) => {
+        $(impl Std430 for $type {
+            fn std430_align() -> usize { $align }
+            fn std430_size() -> usize { mem::size_of::<$type>() }
+            fn write_std430(&self, out: &mut [u8]) { write_raw(self, out) }
+        })*
+    };
+}
+
+std430_raw! {
+    f32: 4, i32: 4, u32: 4, f64: 8,
+    Vector2<f32>: 8, Vector2<i32>: 8, Vector2<u32>: 8, Vector2<f64>: 16,
+    Vector3<f32>: 16, Vector3<i32>: 16, Vector3<u32>: 16, Vector3<f64>: 32,
+    Vector4<f32>: 16, Vector4<i32>: 16, Vector4<u32>: 16, Vector4<f64>: 32,
+    // Without the vec4 rounding, mat2 columns can be packed tightly too.
+    Matrix2<f32>: 8,
+    Matrix4<f32>: 16,
+}
+
+impl Std430 for bool {
+    fn std430_align() -> usize { 4 }
+    fn std430_size() -> usize { 4 }
+    fn write_std430(&self, out: &mut [u8]) { write_raw(&(*self as u32), out) }
+}
+
+impl Std430 for Matrix3<f32> {
+    fn std430_align() -> usize { 16 }
+    fn std430_size() -> usize { 48 }
+    fn write_std430(&self, out: &mut [u8]) {
+        self.x.write_std430(&mut out[0..]);
+        self.y.write_std430(&mut out[16..]);
+        self.z.write_std430(&mut out[32..]);
+    }
+}
+
+macro_rules! std430_array {
+    ($($len:expr),*) => {
+        $(impl<T: Std430> Std430 for [T; $len] {
+            fn std430_align() -> usize { T::std430_align() }
+            fn std430_size() -> usize { $len * T::std430_stride() }
+            fn write_std430(&self, out: &mut [u8]) {
+                for (i, item) in self.iter().enumerate() {
+                    item.write_std430(&mut out[i * T::std430_stride()..]);
+                }
+            }
+        })*
+    };
+}
+
+std430_array!(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 32, 64);
+
+/// Declares a struct with a `std430` layout, for use in shader storage blocks.
+/// Members are laid out in order, same as in GLSL.
+macro_rules! std430 {
+    (struct $name:ident {
+        $($member:ident: $member_type:ty,)*
+    }) => {
+        #[derive(Copy, Clone, Debug)]
+        pub struct $name {
+            $(pub $member: $member_type),*
+        }
+
+        impl ::gl_api::block::Std430 for $name {
+            fn std430_align() -> usize {
+                // The largest alignment of any member.
+                let align = 1;
+                $(let align = align.max(<$member_type as ::gl_api::block::Std430>::std430_align());)*
+                align
+            }
+
+            fn std430_size() -> usize {
+                #[allow(unused_mut)]
+                let mut end = 0;
+                $(
+                    end = ::gl_api::block::align_to(end, <$member_type as ::gl_api::block::Std430>::std430_align());
+                    end += <$member_type as ::gl_api::block::Std430>::std430_size();
+                )*
+                ::gl_api::block::align_to(end, Self::std430_align())
+            }
+
+            #[allow(unused_variables)]
+            fn write_std430(&self, out: &mut [u8]) {
+                let mut offsets = Self::std430_offsets().into_iter();
+                $(
+                    // UNWRAP: there's one offset per member
+                    let (_, offset) = offsets.next().unwrap();
+                    ::gl_api::block::Std430::write_std430(&self.$member, &mut out[offset..]);
+                )*
+            }
+
+            #[allow(unused_mut, unused_assignments)]
+            fn std430_offsets() -> Vec<(&'static str, usize)> {
+                let mut offsets = Vec::new();
+                let mut end = 0;
+                $(
+                    let offset = ::gl_api::block::align_to(end, <$member_type as ::gl_api::block::Std430>::std430_align());
+                    offsets.push((stringify!($member), offset));
+                    end = offset + <$member_type as ::gl_api::block::Std430>::std430_size();
+                )*
+                offsets
+            }
+        }
+    };
+}
+
+/// A uniform buffer holding a single `T`.
+pub struct UniformBlock<T> {
+    buffer: IndexedBuffer<u8, buffer::Uniform>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Std140Block> UniformBlock<T> {
+    crate fn new(bind_point: u32) -> GlResult<Self> {
+        Ok(UniformBlock { buffer: IndexedBuffer::new(bind_point)?, _marker: PhantomData })
+    }
+
+    /// Binds the buffer to the block's binding point. Only needed if another
+    /// program has used the same binding point since the last `set`.
+    pub fn bind(&self) -> GlResult<()> {
+        self.buffer.bind()
+    }
+
+    pub fn set(&mut self, value: &T) -> GlResult<()> {
+        let mut bytes = vec![0; T::std140_size()];
+        value.write_std140(&mut bytes);
+        self.buffer.upload(&bytes, UsageType::DynamicDraw)
+    }
+}
+
+/// A shader storage buffer holding an array of `T`.
+pub struct StorageBuffer<T> {
+    buffer: IndexedBuffer<u8, buffer::ShaderStorage>,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Std430> StorageBuffer<T> {
+    crate fn new(bind_point: u32) -> GlResult<Self> {
+        Ok(StorageBuffer { buffer: IndexedBuffer::new(bind_point)?, len: 0, _marker: PhantomData })
+    }
+
+    pub fn bind(&self) -> GlResult<()> {
+        self.buffer.bind()
+    }
+
+    /// How many `T`s are in the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// See `Buffer::is_corrupted`.
+    pub fn is_corrupted(&self) -> bool {
+        self.buffer.is_corrupted()
+    }
+
+    pub fn set_label(&self, label: &str) -> GlResult<()> {
+        self.buffer.set_label(label)
+    }
+
+    pub fn upload(&mut self, data: &[T], usage_type: UsageType) -> GlResult<()> {
+        let stride = T::std430_stride();
+        let mut bytes = vec![0; stride * data.len()];
+        for (i, item) in data.iter().enumerate() {
+            item.write_std430(&mut bytes[i * stride..]);
+        }
+        self.len = data.len();
+        self.buffer.upload(&bytes, usage_type)
+    }
+
+    pub fn map_mut<'b>(&'b mut self) -> Result<StorageMapMut<'b, T>, BufferMapError> {
+        Ok(StorageMapMut { map: self.buffer.map_mut()?, _marker: PhantomData })
+    }
+}
+
+pub struct StorageMapMut<'b, T> {
+    map: BufferMapMut<'b, u8, buffer::ShaderStorage>,
+    _marker: PhantomData<T>,
+}
+
+impl<'b, T: Std430> StorageMapMut<'b, T> {
+    pub fn set(&mut self, index: usize, value: &T) {
+        let stride = T::std430_stride();
+        let bytes = self.map.as_mut_slice();
+        assert!((index + 1) * stride <= bytes.len());
+        value.write_std430(&mut bytes[index * stride..]);
+    }
+
+    /// See `BufferMapMut::unmap`.
+    pub fn unmap(self) -> Result<(), BufferMapError> {
+        self.map.unmap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    std140! {
+        block Inner {
+            a: f32,
+            b: Vector2<f32>,
+        }
+    }
+
+    std140! {
+        block Example {
+            a: f32,
+            b: Vector3<f32>,
+            c: f32,
+            d: [f32; 2],
+            e: Matrix3<f32>,
+            f: Inner,
+            g: bool,
+        }
+    }
+
+    #[test]
+    fn std140_offsets() {
+        assert_eq!(Inner::std140_offsets(), vec![("a", 0), ("b", 8)]);
+        assert_eq!(Inner::std140_size(), 16);
+
+        // A float can go right after a vec3, but arrays and structs have to
+        // start on a vec4 boundary.
+        assert_eq!(Example::std140_offsets(), vec![
+            ("a", 0),
+            ("b", 16),
+            ("c", 28),
+            ("d", 32),
+            ("e", 64),
+            ("f", 112),
+            ("g", 128),
+        ]);
+        assert_eq!(Example::std140_size(), 144);
+    }
+
+    #[test]
+    fn std140_write() {
+        let example = Example {
+            a: 1.0,
+            b: Vector3::new(2.0, 3.0, 4.0),
+            c: 5.0,
+            d: [6.0, 7.0],
+            e: Matrix3::new(8.0, 9.0, 10.0, 11.0, 12.0, 13.0, 14.0, 15.0, 16.0),
+            f: Inner { a: 17.0, b: Vector2::new(18.0, 19.0) },
+            g: true,
+        };
+        let mut bytes = vec![0; Example::std140_size()];
+        example.write_std140(&mut bytes);
+
+        let floats = bytes.chunks(4)
+            .map(|chunk| unsafe { ptr::read_unaligned(chunk.as_ptr() as *const f32) })
+            .collect::<Vec<_>>();
+        assert_eq!(&floats[0..8], &[1.0, 0.0, 0.0, 0.0, 2.0, 3.0, 4.0, 5.0]);
+        // Array elements and matrix columns are each padded out to a vec4.
+        assert_eq!((floats[8], floats[12]), (6.0, 7.0));
+        assert_eq!(&floats[16..20], &[8.0, 9.0, 10.0, 0.0]);
+        assert_eq!(&floats[24..28], &[14.0, 15.0, 16.0, 0.0]);
+        assert_eq!(&floats[28..32], &[17.0, 0.0, 18.0, 19.0]);
+        assert_eq!(&bytes[128..132], &[1, 0, 0, 0][..]);
+    }
+
+    std430! {
+        struct Packed {
+            a: f32,
+            b: [f32; 3],
+            c: Vector3<f32>,
+            d: f32,
+            e: Vector2<f32>,
+        }
+    }
+
+    #[test]
+    fn std430_layout() {
+        // Unlike std140, the array doesn't get bumped up to a vec4 boundary.
+        assert_eq!(Packed::std430_offsets(), vec![("a", 0), ("b", 4), ("c", 16), ("d", 28), ("e", 32)]);
+        assert_eq!(Packed::std430_size(), 48);
+        assert_eq!(Packed::std430_stride(), 48);
+        assert_eq!(<[Packed; 2]>::std430_size(), 96);
+
+        let packed = Packed { a: 1.0, b: [2.0, 3.0, 4.0], c: Vector3::new(5.0, 6.0, 7.0), d: 8.0, e: Vector2::new(9.0, 10.0) };
+        let mut bytes = vec![0; Packed::std430_size()];
+        packed.write_std430(&mut bytes);
+
+        let floats = bytes.chunks(4)
+            .map(|chunk| unsafe { ptr::read_unaligned(chunk.as_ptr() as *const f32) })
+            .collect::<Vec<_>>();
+        assert_eq!(&floats[0..10], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0]);
+    }
+}
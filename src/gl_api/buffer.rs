@@ -1,7 +1,10 @@
+use gl_api::debug;
 use gl_api::error::GlError;
 use super::error::GlResult;
 use gl;
 use gl::types::*;
+use std::error;
+use std::fmt;
 use std::marker::PhantomData;
 
 mod sealed {
@@ -42,6 +45,7 @@ buffer_target!(ShaderStorage: indexed gl::SHADER_STORAGE_BUFFER);
 buffer_target!(Texture: gl::TEXTURE_BUFFER);
 buffer_target!(TransformFeedback: indexed gl::TRANSFORM_FEEDBACK_BUFFER);
 buffer_target!(AtomicCounter: indexed gl::ATOMIC_COUNTER_BUFFER);
+buffer_target!(DispatchIndirect: gl::DISPATCH_INDIRECT_BUFFER);
 
 // Values from section 6.2 of spec
 /// Usage type for buffers, provided as a performance hint. These values do not affect the behavior
@@ -73,6 +77,7 @@ pub enum UsageType {
 pub struct Buffer<T, B: BufferTarget> {
     pub(crate) id: GLuint,
     length: usize,
+    corrupted: bool,
     _phantom: PhantomData<(*mut T, B)>,
 }
 
@@ -83,30 +88,29 @@ pub struct IndexedBuffer<T, B: IndexedTarget> {
 }
 
 impl<T, B: BufferTarget> Buffer<T, B> {
-    pub fn new() -> Self {
+    pub fn new() -> GlResult<Self> {
         let mut id = 0;
-        // UNWRAP: Could only error if the amount is negative
+        // Could only error if the amount is negative
         unsafe {
-            gl_call!(GenBuffers(1, &mut id)).unwrap();
+            gl_call!(GenBuffers(1, &mut id))?;
         }
-        Buffer {
+        Ok(Buffer {
             id,
             length: 0,
+            corrupted: false,
             _phantom: PhantomData,
-        }
+        })
     }
 
-    pub fn bind(&self) {
-        // UNWRAP: Could only error if the buffer type is invalid
-        unsafe {
-            gl_call!(BindBuffer(B::TARGET, self.id)).unwrap();
-        }
+    pub fn bind(&self) -> GlResult<()> {
+        // Could only error if the buffer type is invalid
+        unsafe { gl_call!(BindBuffer(B::TARGET, self.id)) }
     }
 
     /// Copies data from `data` to the gpu's memory
     pub fn upload(&mut self, data: &[T], usage_type: UsageType) -> GlResult<()> {
         unsafe {
-            self.bind();
+            self.bind()?;
             self.length = data.len();
             // Could fail if OOM
             gl_call!(BufferData(
@@ -114,44 +118,66 @@ impl<T, B: BufferTarget> Buffer<T, B> {
                 (::std::mem::size_of::<T>() * data.len()) as isize,
                 data.as_ptr() as *const _,
                 usage_type as GLenum
-            ))
+            ))?;
         }
+        self.corrupted = false;
+        Ok(())
     }
 
     pub fn len(&self) -> usize {
         self.length
     }
+
+    /// Whether the contents got lost while the buffer was mapped, which can
+    /// happen when the screen mode changes, for example. Mappings that get
+    /// dropped instead of `unmap`ped can only say so through this. Uploading
+    /// everything again clears it.
+    pub fn is_corrupted(&self) -> bool {
+        self.corrupted
+    }
+
+    /// Names the buffer in debug messages.
+    pub fn set_label(&self, label: &str) -> GlResult<()> {
+        // Buffers don't exist until they're first bound
+        self.bind()?;
+        debug::label_object(gl::BUFFER, self.id, label);
+        Ok(())
+    }
 }
 
 impl<T, B: BufferTarget> Drop for Buffer<T, B> {
     fn drop(&mut self) {
         unsafe {
-            // UNWRAP: can only fail if count is negative, which it isn't
-            gl_call!(DeleteBuffers(1, &self.id)).unwrap();
+            // Nothing to be done about an error here, and panicking in a drop
+            // could abort if it happens while unwinding
+            let _ = gl_call!(DeleteBuffers(1, &self.id));
         }
     }
 }
 
 impl<T, B: IndexedTarget> IndexedBuffer<T, B> {
-    crate fn new(bind_point: GLuint) -> Self {
-        IndexedBuffer { buf: Buffer::new(), bind_point }
+    crate fn new(bind_point: GLuint) -> GlResult<Self> {
+        Ok(IndexedBuffer { buf: Buffer::new()?, bind_point })
     }
 
-    pub fn bind(&self) {
-        unsafe {
-            // TODO: unwrap
-            gl_call!(BindBufferBase(B::TARGET, self.bind_point, self.buf.id)).unwrap();
-        }
+    pub fn bind(&self) -> GlResult<()> {
+        // Fails if the bind point is past the number of bind points for the
+        // target
+        unsafe { gl_call!(BindBufferBase(B::TARGET, self.bind_point, self.buf.id)) }
     }
 
     pub fn upload(&mut self, data: &[T], usage_type: UsageType) -> GlResult<()> {
-        self.bind();
+        self.bind()?;
         self.buf.upload(data, usage_type)
     }
 
     pub fn len(&self) -> usize { self.buf.len() }
 
-    pub fn map_mut<'b>(&'b mut self) -> GlResult<Option<BufferMapMut<'b, T, B>>> {
+    pub fn is_corrupted(&self) -> bool { self.buf.is_corrupted() }
+
+    pub fn set_label(&self, label: &str) -> GlResult<()> { self.buf.set_label(label) }
+
+    pub fn map_mut<'b>(&'b mut self) -> Result<BufferMapMut<'b, T, B>, BufferMapError> {
         BufferMapMut::new(&mut self.buf)
     }
 }
@@ -160,34 +186,78 @@ impl<T, B: IndexedTarget> IndexedBuffer<T, B> {
 pub enum BufferMapError {
     Gl(GlError),
     ZeroLength,
+    /// The contents of the buffer got lost while it was mapped, and need to be
+    /// uploaded again.
+    Corrupted,
 }
 
 impl From<GlError> for BufferMapError {
     fn from(err: GlError) -> Self { BufferMapError::Gl(err) }
 }
 
+impl fmt::Display for BufferMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BufferMapError::Gl(ref err) => write!(f, "{}", err),
+            BufferMapError::ZeroLength => write!(f, "can't map a buffer with nothing in it"),
+            BufferMapError::Corrupted => write!(f, "the buffer's contents were lost while it was mapped"),
+        }
+    }
+}
+
+impl error::Error for BufferMapError {
+    fn description(&self) -> &str {
+        "couldn't map a buffer"
+    }
+
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            BufferMapError::Gl(ref err) => error::Error::source(err),
+            _ => None,
+        }
+    }
+}
+
+/// A buffer's contents, mapped into our memory. Call `unmap` when done with it
+/// to find out whether the writes actually made it.
 pub struct BufferMapMut<'b, T: 'b, B: BufferTarget + 'b> {
     buf: &'b mut Buffer<T, B>,
     mapped: *mut T,
 }
 
 impl<'b, T: 'b, B: BufferTarget + 'b> BufferMapMut<'b, T, B> {
-    crate fn new(buf: &'b mut Buffer<T, B>) -> GlResult<Option<Self>> {
-        unsafe {
-            buf.bind();
-            let mut mapped = 0;
-            let mut ptr = ::std::ptr::null_mut();
-            gl_call!(GetBufferParameteriv(B::TARGET, gl::BUFFER_MAPPED, &mut mapped)).unwrap();
-            gl_call!(GetBufferPointerv(B::TARGET, gl::BUFFER_MAP_POINTER, &mut ptr)).unwrap();
-            assert!(mapped == 0);
-            assert!(ptr.is_null());
-            assert!(buf.id != 0);
-            assert!(buf.len() > 0);
-            if buf.len() > 0 {
-                let ptr = gl_call!(MapBufferRange(B::TARGET, 0, buf.len() as isize, gl::MAP_READ_BIT | gl::MAP_WRITE_BIT))?;
-                Ok(Some(BufferMapMut { buf, mapped: ptr as *mut T }))
-            } else { Ok(None) }
+    crate fn new(buf: &'b mut Buffer<T, B>) -> Result<Self, BufferMapError> {
+        if buf.len() == 0 {
+            return Err(BufferMapError::ZeroLength);
         }
+
+        buf.bind()?;
+        let size = (buf.len() * ::std::mem::size_of::<T>()) as isize;
+        // Can't already be mapped, since that would need another borrow of `buf`
+        let ptr = unsafe { gl_call!(MapBufferRange(B::TARGET, 0, size, gl::MAP_READ_BIT | gl::MAP_WRITE_BIT))? };
+        Ok(BufferMapMut { buf, mapped: ptr as *mut T })
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { ::std::slice::from_raw_parts_mut(self.mapped, self.buf.len()) }
+    }
+
+    /// Gives the buffer back to GL. If this says the buffer was corrupted,
+    /// nothing that was written to it can be relied on, and everything has to
+    /// be uploaded again.
+    pub fn unmap(mut self) -> Result<(), BufferMapError> {
+        let result = self.unmap_in_place();
+        ::std::mem::forget(self);
+        result
+    }
+
+    fn unmap_in_place(&mut self) -> Result<(), BufferMapError> {
+        self.buf.bind()?;
+        if unsafe { gl_call!(UnmapBuffer(B::TARGET))? } == gl::FALSE {
+            self.buf.corrupted = true;
+            return Err(BufferMapError::Corrupted);
+        }
+        Ok(())
     }
 }
 
@@ -208,20 +278,24 @@ impl<'b, T: 'b, B: BufferTarget + 'b> IndexMut<usize> for BufferMapMut<'b, T, B>
     }
 }
 
+// There's nowhere to report anything from here, so a corrupted store just gets
+// remembered by the buffer. See `Buffer::is_corrupted`.
 impl<'b, T: 'b, B: BufferTarget + 'b> Drop for BufferMapMut<'b, T, B> {
     fn drop(&mut self) {
-        unsafe {
-            self.buf.bind();
-            if gl_call!(UnmapBuffer(B::TARGET)).unwrap() == 0 {
-                // The data store is in an undefined state, wat to do???
-                // TODO: Do something other than panic.
-                // FIXME: NOT GOOD.
-                panic!("Buffer data store was corrupted while it was mapped.");
-            }
-        }
+        let _ = self.unmap_in_place();
     }
 }
 
 pub type VertexBuffer<T> = Buffer<T, Array>;
 pub type ElementBuffer<T> = Buffer<T, Element>;
 pub type ShaderStorageBuffer<T> = IndexedBuffer<T, ShaderStorage>;
+pub type DispatchIndirectBuffer = Buffer<DispatchIndirectCommand, DispatchIndirect>;
+
+/// Laid out the way `glDispatchComputeIndirect` expects.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+#[repr(C)]
+pub struct DispatchIndirectCommand {
+    pub num_groups_x: u32,
+    pub num_groups_y: u32,
+    pub num_groups_z: u32,
+}
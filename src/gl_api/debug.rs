@@ -0,0 +1,336 @@
+use gl;
+use gl::types::*;
+use gl_api::error::GlResult;
+use std::fmt;
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+// GL_MAX_LABEL_LENGTH is at least 256, and labels have to be shorter than it.
+const MAX_LABEL_LENGTH: usize = 255;
+
+macro_rules! gl_enum {
+    ($(#[$meta:meta])* pub enum $name:ident { $($variant:ident = $gl:ident,)* }) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+        pub enum $name {
+            $($variant,)*
+            /// Something newer than this code.
+            Unknown(GLenum),
+        }
+
+        impl $name {
+            fn from_gl(value: GLenum) -> Self {
+                match value {
+                    $(gl::$gl => $name::$variant,)*
+                    other => $name::Unknown(other),
+                }
+            }
+        }
+    };
+}
+
+gl_enum! {
+    /// What generated a debug message.
+    pub enum Source {
+        Api = DEBUG_SOURCE_API,
+        WindowSystem = DEBUG_SOURCE_WINDOW_SYSTEM,
+        ShaderCompiler = DEBUG_SOURCE_SHADER_COMPILER,
+        ThirdParty = DEBUG_SOURCE_THIRD_PARTY,
+        Application = DEBUG_SOURCE_APPLICATION,
+        Other = DEBUG_SOURCE_OTHER,
+    }
+}
+
+gl_enum! {
+    pub enum MessageType {
+        Error = DEBUG_TYPE_ERROR,
+        DeprecatedBehavior = DEBUG_TYPE_DEPRECATED_BEHAVIOR,
+        UndefinedBehavior = DEBUG_TYPE_UNDEFINED_BEHAVIOR,
+        Portability = DEBUG_TYPE_PORTABILITY,
+        Performance = DEBUG_TYPE_PERFORMANCE,
+        Marker = DEBUG_TYPE_MARKER,
+        PushGroup = DEBUG_TYPE_PUSH_GROUP,
+        PopGroup = DEBUG_TYPE_POP_GROUP,
+        Other = DEBUG_TYPE_OTHER,
+    }
+}
+
+/// How much a debug message matters. These are ordered from least to most
+/// severe.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Severity {
+    /// Anything that isn't a problem, like which memory a buffer ended up in.
+    Notification,
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 4] = [Severity::Notification, Severity::Low, Severity::Medium, Severity::High];
+
+    fn from_gl(severity: GLenum) -> Self {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH => Severity::High,
+            gl::DEBUG_SEVERITY_MEDIUM => Severity::Medium,
+            gl::DEBUG_SEVERITY_LOW => Severity::Low,
+            _ => Severity::Notification,
+        }
+    }
+
+    fn to_gl(self) -> GLenum {
+        match self {
+            Severity::High => gl::DEBUG_SEVERITY_HIGH,
+            Severity::Medium => gl::DEBUG_SEVERITY_MEDIUM,
+            Severity::Low => gl::DEBUG_SEVERITY_LOW,
+            Severity::Notification => gl::DEBUG_SEVERITY_NOTIFICATION,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Severity::High => "high",
+            Severity::Medium => "medium",
+            Severity::Low => "low",
+            Severity::Notification => "notification",
+        })
+    }
+}
+
+/// Something the driver had to say about what we asked it to do.
+#[derive(Clone, Debug)]
+pub struct DebugMessage {
+    pub source: Source,
+    pub ty: MessageType,
+    /// Only means anything together with `source` and `ty`, and it's up to the
+    /// driver what it means.
+    pub id: GLuint,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for DebugMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {:?} {:?} {}: {}", self.severity, self.source, self.ty, self.id, self.message)
+    }
+}
+
+/// Where debug messages end up. Each severity can go to a different sink, and
+/// severities without one are turned off entirely so the driver doesn't have
+/// to bother with them.
+pub struct DebugOutput {
+    sinks: Vec<(Severity, Box<Fn(&DebugMessage)>)>,
+}
+
+impl DebugOutput {
+    /// Doesn't send anything anywhere until some severities are routed.
+    pub fn new() -> Self {
+        DebugOutput { sinks: Vec::new() }
+    }
+
+    /// Prints everything but notifications to stderr. Some drivers send a
+    /// notification for every buffer upload, so those would drown everything
+    /// else out.
+    pub fn stderr() -> Self {
+        let print = |message: &DebugMessage| eprintln!("gl: {}", message);
+        DebugOutput::new()
+            .route(Severity::High, print)
+            .route(Severity::Medium, print)
+            .route(Severity::Low, print)
+    }
+
+    /// Sends messages of `severity` to `sink`, instead of wherever they went
+    /// before.
+    pub fn route<F: Fn(&DebugMessage) + 'static>(mut self, severity: Severity, sink: F) -> Self {
+        self.sinks.retain(|&(routed, _)| routed != severity);
+        self.sinks.push((severity, Box::new(sink)));
+        self
+    }
+
+    fn routes(&self, severity: Severity) -> bool {
+        self.sinks.iter().any(|&(routed, _)| routed == severity)
+    }
+
+    fn send(&self, message: &DebugMessage) {
+        for &(_, ref sink) in self.sinks.iter().filter(|&&(routed, _)| routed == message.severity) {
+            sink(message);
+        }
+    }
+
+    /// Starts sending the driver's messages to the sinks, until the returned
+    /// `DebugCallback` is dropped. Drivers only say much of anything with a
+    /// debug context. Messages are sent synchronously, so a sink runs inside
+    /// whichever GL call it's about, and a backtrace from it will point right
+    /// at the problem. Sinks are called from C, so if one panics, the panic
+    /// gets caught there and the message is dropped.
+    pub fn install(self) -> GlResult<DebugCallback> {
+        let output = Box::new(self);
+        unsafe {
+            gl_call!(Enable(gl::DEBUG_OUTPUT))?;
+            gl_call!(Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS))?;
+            for &severity in Severity::ALL.iter() {
+                let enabled = if output.routes(severity) { gl::TRUE } else { gl::FALSE };
+                gl_call!(DebugMessageControl(gl::DONT_CARE, gl::DONT_CARE, severity.to_gl(), 0, ptr::null(), enabled))?;
+            }
+            gl_call!(DebugMessageCallback(callback as GLDEBUGPROC, &*output as *const DebugOutput as *const c_void))?;
+        }
+        Ok(DebugCallback { _output: output, _marker: PhantomData })
+    }
+}
+
+/// Keeps debug messages going to a `DebugOutput` while it's alive.
+pub struct DebugCallback {
+    // GL holds onto a pointer to this, so it can't move.
+    _output: Box<DebugOutput>,
+    _marker: PhantomData<*mut ()>,
+}
+
+impl Drop for DebugCallback {
+    fn drop(&mut self) {
+        // Panicking in here could abort the whole program if it's already
+        // unwinding, and there's nothing left to do about an error anyways.
+        unsafe {
+            let _ = gl_call!(Disable(gl::DEBUG_OUTPUT));
+            // There's no way to take the callback away, so it gets told there
+            // isn't anywhere to send messages instead.
+            let _ = gl_call!(DebugMessageCallback(callback as GLDEBUGPROC, ptr::null()));
+        }
+    }
+}
+
+extern "system" fn callback(
+    source: GLenum,
+    ty: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    output: *mut c_void,
+) {
+    if output.is_null() || message.is_null() {
+        return;
+    }
+    let (output, text) = unsafe {
+        (&*(output as *const DebugOutput), slice::from_raw_parts(message as *const u8, length as usize))
+    };
+    let message = DebugMessage {
+        source: Source::from_gl(source),
+        ty: MessageType::from_gl(ty),
+        id,
+        severity: Severity::from_gl(severity),
+        message: String::from_utf8_lossy(text).trim_right().to_owned(),
+    };
+    // Unwinding into the driver is undefined behavior. The panic message has
+    // already been printed by the time it's caught, so there's nothing else to
+    // do with it.
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| output.send(&message)));
+}
+
+/// Names a GL object, so debug messages and tools like RenderDoc can say which
+/// one they mean. Objects have to have been bound at least once before they
+/// can be labelled.
+crate fn label_object(identifier: GLenum, id: GLuint, label: &str) {
+    let label = truncate_label(label);
+    unsafe {
+        // Labels are just for debugging, so it's not worth failing over one
+        let _ = gl_call!(ObjectLabel(identifier, id, label.len() as GLsizei, label.as_ptr() as *const GLchar));
+    }
+}
+
+// Cuts `label` down to something GL will take, without splitting a character.
+fn truncate_label(label: &str) -> &str {
+    let mut length = label.len().min(MAX_LABEL_LENGTH);
+    while !label.is_char_boundary(length) {
+        length -= 1;
+    }
+    &label[..length]
+}
+
+/// Groups everything until it's dropped under `name`, in debug messages and in
+/// frame captures.
+pub struct DebugGroup {
+    // Popping a group that never got pushed would pop someone else's instead.
+    pushed: bool,
+    _marker: PhantomData<*mut ()>,
+}
+
+pub fn group(name: &str) -> DebugGroup {
+    let name = truncate_label(name);
+    // Only fails if the groups are nested more than 64 deep or so, and they're
+    // just for debugging anyways
+    let pushed = unsafe {
+        gl_call!(PushDebugGroup(gl::DEBUG_SOURCE_APPLICATION, 0, name.len() as GLsizei, name.as_ptr() as *const GLchar)).is_ok()
+    };
+    DebugGroup { pushed, _marker: PhantomData }
+}
+
+impl Drop for DebugGroup {
+    fn drop(&mut self) {
+        if self.pushed {
+            unsafe {
+                let _ = gl_call!(PopDebugGroup());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn message(severity: Severity) -> DebugMessage {
+        DebugMessage { source: Source::Api, ty: MessageType::Error, id: 1282, severity, message: "invalid operation".into() }
+    }
+
+    #[test]
+    fn routes_by_severity() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let (high, low) = (seen.clone(), seen.clone());
+        let output = DebugOutput::new()
+            .route(Severity::High, move |message| high.borrow_mut().push(("high", message.severity)))
+            .route(Severity::Low, |_| panic!("replaced sinks shouldn't get anything"))
+            .route(Severity::Low, move |message| low.borrow_mut().push(("low", message.severity)));
+
+        for &severity in Severity::ALL.iter() {
+            output.send(&message(severity));
+        }
+        assert_eq!(*seen.borrow(), vec![("low", Severity::Low), ("high", Severity::High)]);
+        assert!(!output.routes(Severity::Notification));
+        assert!(!DebugOutput::stderr().routes(Severity::Notification));
+    }
+
+    #[test]
+    fn reads_gl_enums() {
+        assert_eq!(Source::from_gl(gl::DEBUG_SOURCE_SHADER_COMPILER), Source::ShaderCompiler);
+        assert_eq!(MessageType::from_gl(0x1234), MessageType::Unknown(0x1234));
+        assert!(Severity::from_gl(gl::DEBUG_SEVERITY_HIGH) > Severity::from_gl(gl::DEBUG_SEVERITY_NOTIFICATION));
+        assert_eq!(message(Severity::High).to_string(), "[high] Api Error 1282: invalid operation");
+    }
+
+    #[test]
+    fn labels_are_cut_between_characters() {
+        assert_eq!(truncate_label("tileset"), "tileset");
+        let long = "é".repeat(200);
+        let cut = truncate_label(&long);
+        assert_eq!(cut.len(), 254);
+        assert!(cut.chars().all(|ch| ch == 'é'));
+    }
+
+    #[test]
+    fn panicking_sinks_stay_out_of_the_driver() {
+        let output = DebugOutput::new().route(Severity::High, |_| panic!("sink blew up"));
+        let text = "invalid operation";
+        callback(
+            gl::DEBUG_SOURCE_API, gl::DEBUG_TYPE_ERROR, 1282, gl::DEBUG_SEVERITY_HIGH,
+            text.len() as GLsizei, text.as_ptr() as *const GLchar,
+            &output as *const DebugOutput as *mut c_void,
+        );
+    }
+}
@@ -1,29 +1,248 @@
 use gl::types::GLenum;
 use gl;
+use gl_api::buffer::BufferMapError;
+use gl_api::framebuffer::FramebufferError;
+use gl_api::shader::PipelineError;
+use gl_api::shader::program::ProgramError;
+use gl_api::shader::shader::ShaderError;
+use gl_api::texture::TextureError;
+use std::error;
+use std::fmt;
+use std::io;
 
 pub type GlResult<T> = Result<T, GlError>;
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+/// Where a GL call was made, as captured by `gl_call!`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct CallSite {
+    /// The GL function without the `gl` prefix, like `BindBuffer`.
+    pub function: &'static str,
+    /// What the arguments were when the call failed, formatted with `Debug`.
+    /// This only gets filled in once the call has actually failed, so calls
+    /// that work don't pay for it.
+    pub args: String,
+    pub file: &'static str,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl fmt::Display for CallSite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "gl{}({}) at {}:{}:{}", self.function, self.args, self.file, self.line, self.column)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct GlError {
     code: GLenum,
+    site: CallSite,
 }
 
 impl GlError {
     fn get_raw() -> GLenum { unsafe { gl::GetError() } }
 
-    pub fn map_value<T>(val: T) -> GlResult<T> {
+    /// `site` only gets called if there was an error.
+    pub fn map_value<T, S: FnOnce() -> CallSite>(val: T, site: S) -> GlResult<T> {
         match Self::get_raw() {
             0 => Ok(val),
             // GL specification states that it is undefined to issue any GL
             // calls after an out of memory error is received.
-            gl::OUT_OF_MEMORY => ::std::process::abort(),
-            code => Err(GlError { code }),
+            gl::OUT_OF_MEMORY => {
+                eprintln!("out of GPU memory in {}", site());
+                ::std::process::abort()
+            }
+            code => Err(GlError { code, site: site() }),
         }
     }
+
+    pub fn code(&self) -> GLenum {
+        self.code
+    }
+
+    /// The call that failed.
+    pub fn site(&self) -> &CallSite {
+        &self.site
+    }
+
+    /// What the error is called in the spec, like `GL_INVALID_ENUM`.
+    pub fn name(&self) -> &'static str {
+        match self.code {
+            gl::INVALID_ENUM => "GL_INVALID_ENUM",
+            gl::INVALID_VALUE => "GL_INVALID_VALUE",
+            gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+            gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+            gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+            gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+            gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+            gl::CONTEXT_LOST => "GL_CONTEXT_LOST",
+            _ => "unknown GL error",
+        }
+    }
+
+    fn meaning(&self) -> &'static str {
+        match self.code {
+            gl::INVALID_ENUM => "an enum argument isn't allowed here",
+            gl::INVALID_VALUE => "a numeric argument is out of range",
+            gl::INVALID_OPERATION => "the call isn't allowed in the current state",
+            gl::INVALID_FRAMEBUFFER_OPERATION => "the framebuffer is incomplete",
+            gl::STACK_OVERFLOW | gl::STACK_UNDERFLOW => "a stack was pushed or popped too far",
+            gl::OUT_OF_MEMORY => "there isn't enough memory left",
+            gl::CONTEXT_LOST => "the context was lost, probably because the GPU was reset",
+            _ => "the driver didn't say what went wrong",
+        }
+    }
+}
+
+impl fmt::Display for GlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (0x{:04X}) from {}: {}", self.name(), self.code, self.site, self.meaning())
+    }
+}
+
+impl error::Error for GlError {
+    fn description(&self) -> &str {
+        self.name()
+    }
 }
 
+/// Calls a GL function, and checks `glGetError` afterwards. Errors remember
+/// which call caused them, what it was called with, and where it was.
 macro_rules! gl_call {
-    ($name:ident($($args:expr),*)) => {{
-        $crate::gl_api::error::GlError::map_value(::gl::$name($($args),*))
-    }}
+    // Each argument gets evaluated once into its own binding, so that it can be
+    // both passed to GL and printed if the call fails. Matching on it instead
+    // of using `let` keeps temporaries alive until the call is done, just like
+    // they would be if it was passed directly.
+    (@bind $name:ident [$($bound:ident)*] $arg:expr, $($rest:tt)*) => {
+        match $arg {
+            arg => gl_call!(@bind $name [$($bound)* arg] $($rest)*),
+        }
+    };
+    (@bind $name:ident [$($bound:ident)*]) => {
+        $crate::gl_api::error::GlError::map_value(::gl::$name($($bound),*), || $crate::gl_api::error::CallSite {
+            function: stringify!($name),
+            args: {
+                let args: &[String] = &[$(format!("{:?}", $bound)),*];
+                args.join(", ")
+            },
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        })
+    };
+    ($name:ident($($args:expr),*)) => {
+        gl_call!(@bind $name [] $($args,)*)
+    };
+}
+
+/// Anything that can go wrong in `gl_api`, for code that doesn't care which
+/// part of it something came from.
+#[derive(Debug)]
+pub enum Error {
+    Gl(GlError),
+    BufferMap(BufferMapError),
+    Texture(TextureError),
+    Framebuffer(FramebufferError),
+    Shader(ShaderError),
+    Program(ProgramError),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Gl(ref err) => write!(f, "{}", err),
+            Error::BufferMap(ref err) => write!(f, "{}", err),
+            Error::Texture(ref err) => write!(f, "{}", err),
+            Error::Framebuffer(ref err) => write!(f, "{}", err),
+            Error::Shader(ref err) => write!(f, "{}", err),
+            Error::Program(ref err) => write!(f, "{}", err),
+            Error::Io(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+// Errors that just wrap another one say the same thing it does, so they pass
+// straight through to whatever caused it instead of showing up in the chain
+// twice. Errors that add something of their own have the wrapped one as their
+// source.
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "error in gl_api"
+    }
+
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            Error::Gl(ref err) => error::Error::source(err),
+            Error::BufferMap(ref err) => error::Error::source(err),
+            Error::Texture(ref err) => error::Error::source(err),
+            Error::Framebuffer(ref err) => error::Error::source(err),
+            Error::Shader(ref err) => error::Error::source(err),
+            Error::Program(ref err) => error::Error::source(err),
+            Error::Io(ref err) => error::Error::source(err),
+        }
+    }
+}
+
+macro_rules! from_error {
+    ($($variant:ident($error:ty),)*) => {
+        $(impl From<$error> for Error {
+            fn from(err: $error) -> Self { Error::$variant(err) }
+        })*
+    };
+}
+
+from_error! {
+    Gl(GlError),
+    BufferMap(BufferMapError),
+    Texture(TextureError),
+    Framebuffer(FramebufferError),
+    Shader(ShaderError),
+    Program(ProgramError),
+    Io(io::Error),
+}
+
+impl From<PipelineError> for Error {
+    fn from(err: PipelineError) -> Self {
+        match err {
+            PipelineError::Shader(err) => Error::Shader(err),
+            PipelineError::Program(err) => Error::Program(err),
+            PipelineError::Io(err) => Error::Io(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as StdError;
+
+    #[test]
+    fn messages_say_where() {
+        let site = CallSite {
+            function: "BindBuffer",
+            args: "34962, 7".into(),
+            file: "src/gl_api/buffer.rs",
+            line: 100,
+            column: 13,
+        };
+        let err = GlError { code: gl::INVALID_ENUM, site };
+        assert_eq!(
+            err.to_string(),
+            "GL_INVALID_ENUM (0x0500) from glBindBuffer(34962, 7) at src/gl_api/buffer.rs:100:13: \
+             an enum argument isn't allowed here"
+        );
+    }
+
+    #[test]
+    fn errors_unify() {
+        let err = Error::from(PipelineError::Io(io::Error::new(io::ErrorKind::NotFound, "res/world.glslf")));
+        match err {
+            Error::Io(_) => (),
+            other => panic!("expected an io error, got {:?}", other),
+        }
+
+        let err = Error::from(ShaderError::Io(io::Error::new(io::ErrorKind::NotFound, "res/world.glslf")));
+        assert!(err.to_string().contains("res/world.glslf"));
+        assert!(err.source().is_some());
+    }
 }
@@ -0,0 +1,316 @@
+use gl;
+use gl::types::*;
+use gl_api::error::{GlError, GlResult};
+use gl_api::texture::{Texture, Texture2D, TextureError, TextureFormat, MagnificationFilter, MinimizationFilter};
+use std::error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum FramebufferError {
+    Gl(GlError),
+    Texture(TextureError),
+    Incomplete(Incompleteness),
+}
+
+impl From<GlError> for FramebufferError {
+    fn from(err: GlError) -> Self { FramebufferError::Gl(err) }
+}
+
+impl From<TextureError> for FramebufferError {
+    fn from(err: TextureError) -> Self { FramebufferError::Texture(err) }
+}
+
+impl fmt::Display for FramebufferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FramebufferError::Gl(ref err) => write!(f, "{}", err),
+            FramebufferError::Texture(ref err) => write!(f, "{}", err),
+            FramebufferError::Incomplete(reason) => write!(f, "the framebuffer is incomplete: {}", reason),
+        }
+    }
+}
+
+impl error::Error for FramebufferError {
+    fn description(&self) -> &str {
+        "couldn't make a framebuffer"
+    }
+
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            FramebufferError::Gl(ref err) => error::Error::source(err),
+            FramebufferError::Texture(ref err) => error::Error::source(err),
+            FramebufferError::Incomplete(_) => None,
+        }
+    }
+}
+
+/// Why `glCheckFramebufferStatus` didn't like a framebuffer. See section 9.4.2
+/// of the spec for what each of these mean.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Incompleteness {
+    Undefined,
+    IncompleteAttachment,
+    MissingAttachment,
+    IncompleteDrawBuffer,
+    IncompleteReadBuffer,
+    Unsupported,
+    IncompleteMultisample,
+    IncompleteLayerTargets,
+    Unknown(GLenum),
+}
+
+impl Incompleteness {
+    fn from_status(status: GLenum) -> Option<Self> {
+        Some(match status {
+            gl::FRAMEBUFFER_COMPLETE => return None,
+            gl::FRAMEBUFFER_UNDEFINED => Incompleteness::Undefined,
+            gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => Incompleteness::IncompleteAttachment,
+            gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => Incompleteness::MissingAttachment,
+            gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => Incompleteness::IncompleteDrawBuffer,
+            gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => Incompleteness::IncompleteReadBuffer,
+            gl::FRAMEBUFFER_UNSUPPORTED => Incompleteness::Unsupported,
+            gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => Incompleteness::IncompleteMultisample,
+            gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => Incompleteness::IncompleteLayerTargets,
+            other => Incompleteness::Unknown(other),
+        })
+    }
+}
+
+impl fmt::Display for Incompleteness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Incompleteness::Undefined => write!(f, "the default framebuffer doesn't exist"),
+            Incompleteness::IncompleteAttachment => write!(f, "an attachment isn't complete"),
+            Incompleteness::MissingAttachment => write!(f, "there aren't any attachments"),
+            Incompleteness::IncompleteDrawBuffer => write!(f, "a draw buffer has nothing attached"),
+            Incompleteness::IncompleteReadBuffer => write!(f, "the read buffer has nothing attached"),
+            Incompleteness::Unsupported => write!(f, "the driver doesn't support this combination of formats"),
+            Incompleteness::IncompleteMultisample => write!(f, "the attachments have different sample counts"),
+            Incompleteness::IncompleteLayerTargets => write!(f, "some attachments are layered and some aren't"),
+            Incompleteness::Unknown(status) => write!(f, "unknown status 0x{:04X}", status),
+        }
+    }
+}
+
+/// Which of the framebuffer binding points to bind to.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[repr(u32)]
+pub enum FramebufferTarget {
+    Draw = gl::DRAW_FRAMEBUFFER,
+    Read = gl::READ_FRAMEBUFFER,
+    /// Both draw and read.
+    Both = gl::FRAMEBUFFER,
+}
+
+/// Where in a framebuffer an image is attached.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Attachment {
+    Color(u32),
+    Depth,
+    Stencil,
+    DepthStencil,
+}
+
+impl Attachment {
+    fn to_gl(&self) -> GLenum {
+        match *self {
+            Attachment::Color(n) => gl::COLOR_ATTACHMENT0 + n,
+            Attachment::Depth => gl::DEPTH_ATTACHMENT,
+            Attachment::Stencil => gl::STENCIL_ATTACHMENT,
+            Attachment::DepthStencil => gl::DEPTH_STENCIL_ATTACHMENT,
+        }
+    }
+}
+
+/// Formats for renderbuffers.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[repr(u32)]
+pub enum RenderbufferFormat {
+    Rgba8 = gl::RGBA8,
+    Depth24 = gl::DEPTH_COMPONENT24,
+    Depth32F = gl::DEPTH_COMPONENT32F,
+    Depth24Stencil8 = gl::DEPTH24_STENCIL8,
+    Stencil8 = gl::STENCIL_INDEX8,
+}
+
+/// An image that can be rendered into, but not sampled from. Good for depth
+/// buffers that only need to exist for depth testing.
+#[derive(Debug)]
+pub struct Renderbuffer {
+    crate id: GLuint,
+}
+
+impl Renderbuffer {
+    pub fn new(width: u32, height: u32, format: RenderbufferFormat) -> GlResult<Self> {
+        let mut id = 0;
+        unsafe {
+            gl_call!(GenRenderbuffers(1, &mut id))?;
+            let renderbuffer = Renderbuffer { id };
+            renderbuffer.bind()?;
+            // Could fail if the size is larger than GL_MAX_RENDERBUFFER_SIZE
+            gl_call!(RenderbufferStorage(gl::RENDERBUFFER, format as GLenum, width as i32, height as i32))?;
+            Ok(renderbuffer)
+        }
+    }
+
+    pub fn bind(&self) -> GlResult<()> {
+        unsafe { gl_call!(BindRenderbuffer(gl::RENDERBUFFER, self.id)) }
+    }
+}
+
+impl Drop for Renderbuffer {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = gl_call!(DeleteRenderbuffers(1, &self.id));
+        }
+    }
+}
+
+#[derive(Debug)]
+enum AttachedImage {
+    Texture(Texture2D),
+    Renderbuffer(Renderbuffer),
+}
+
+/// A framebuffer that owns the images attached to it. Use `FramebufferBuilder`
+/// to make one.
+#[derive(Debug)]
+pub struct Framebuffer {
+    crate id: GLuint,
+    width: u32,
+    height: u32,
+    attachments: Vec<(Attachment, AttachedImage)>,
+}
+
+impl Framebuffer {
+    pub fn bind(&self, target: FramebufferTarget) -> GlResult<()> {
+        unsafe { gl_call!(BindFramebuffer(target as GLenum, self.id)) }
+    }
+
+    /// Goes back to drawing to (or reading from) the window.
+    pub fn bind_default(target: FramebufferTarget) -> GlResult<()> {
+        unsafe { gl_call!(BindFramebuffer(target as GLenum, 0)) }
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The texture at `attachment`, if there's a texture there and not a
+    /// renderbuffer.
+    pub fn texture(&self, attachment: Attachment) -> Option<&Texture2D> {
+        self.attachments.iter().find(|&&(at, _)| at == attachment).and_then(|&(_, ref image)| match *image {
+            AttachedImage::Texture(ref texture) => Some(texture),
+            AttachedImage::Renderbuffer(_) => None,
+        })
+    }
+
+    /// Checks whether the framebuffer can be drawn to.
+    pub fn check(&self) -> Result<(), FramebufferError> {
+        self.bind(FramebufferTarget::Both)?;
+        let status = unsafe { gl_call!(CheckFramebufferStatus(gl::FRAMEBUFFER))? };
+        match Incompleteness::from_status(status) {
+            Some(reason) => Err(FramebufferError::Incomplete(reason)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = gl_call!(DeleteFramebuffers(1, &self.id));
+        }
+    }
+}
+
+enum ImageDescription {
+    Texture(TextureFormat),
+    Renderbuffer(RenderbufferFormat),
+}
+
+pub struct FramebufferBuilder {
+    width: u32,
+    height: u32,
+    attachments: Vec<(Attachment, ImageDescription)>,
+}
+
+impl FramebufferBuilder {
+    pub fn new(width: u32, height: u32) -> Self {
+        FramebufferBuilder { width, height, attachments: Vec::new() }
+    }
+
+    /// Attaches a new texture, which can be sampled from once something's been
+    /// drawn into it.
+    pub fn texture(mut self, attachment: Attachment, format: TextureFormat) -> Self {
+        self.attachments.push((attachment, ImageDescription::Texture(format)));
+        self
+    }
+
+    pub fn renderbuffer(mut self, attachment: Attachment, format: RenderbufferFormat) -> Self {
+        self.attachments.push((attachment, ImageDescription::Renderbuffer(format)));
+        self
+    }
+
+    /// Creates the framebuffer and all of its images, and makes sure it's
+    /// complete.
+    pub fn build(self) -> Result<Framebuffer, FramebufferError> {
+        let mut id = 0;
+        unsafe {
+            gl_call!(GenFramebuffers(1, &mut id))?;
+        }
+        let mut framebuffer = Framebuffer {
+            id,
+            width: self.width,
+            height: self.height,
+            attachments: Vec::with_capacity(self.attachments.len()),
+        };
+        framebuffer.bind(FramebufferTarget::Both)?;
+
+        for (attachment, description) in self.attachments {
+            let image = match description {
+                ImageDescription::Texture(format) => {
+                    let texture = Texture2D::new()?;
+                    texture.allocate(self.width, self.height, format)?;
+                    // Without mipmaps, the default filter would leave the
+                    // texture incomplete.
+                    texture.min_filter(MinimizationFilter::Linear)?;
+                    texture.mag_filter(MagnificationFilter::Linear)?;
+                    unsafe {
+                        gl_call!(FramebufferTexture2D(gl::FRAMEBUFFER, attachment.to_gl(), gl::TEXTURE_2D, texture.id, 0))?;
+                    }
+                    AttachedImage::Texture(texture)
+                }
+                ImageDescription::Renderbuffer(format) => {
+                    let renderbuffer = Renderbuffer::new(self.width, self.height, format)?;
+                    unsafe {
+                        gl_call!(FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment.to_gl(), gl::RENDERBUFFER, renderbuffer.id))?;
+                    }
+                    AttachedImage::Renderbuffer(renderbuffer)
+                }
+            };
+            framebuffer.attachments.push((attachment, image));
+        }
+
+        // Fragment outputs go to the color attachments in order.
+        let mut draw_buffers = framebuffer.attachments.iter()
+            .filter_map(|&(attachment, _)| match attachment {
+                Attachment::Color(_) => Some(attachment.to_gl()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        draw_buffers.sort();
+        unsafe {
+            if draw_buffers.is_empty() {
+                gl_call!(DrawBuffer(gl::NONE))?;
+                gl_call!(ReadBuffer(gl::NONE))?;
+            } else {
+                gl_call!(DrawBuffers(draw_buffers.len() as i32, draw_buffers.as_ptr()))?;
+            }
+        }
+
+        framebuffer.check()?;
+        Framebuffer::bind_default(FramebufferTarget::Both)?;
+        Ok(framebuffer)
+    }
+}
@@ -1,4 +1,5 @@
 use gl_api::error::GlResult;
+use gl_api::shader::reflect::GlslType;
 use cgmath::{Vector2, Vector3, Vector4};
 
 pub unsafe trait VertexAttribute {
@@ -7,6 +8,9 @@ pub unsafe trait VertexAttribute {
     /// in this layout. This is used to calculate the offset of each attribute
     /// index.
     fn define_attribs(base_slot: u32, offset: u32) -> GlResult<u32>;
+    /// What each of the attributes looks like from the shader's side, in slot
+    /// order.
+    fn attrib_types() -> Vec<GlslType>;
     const NUM_ATTRS: usize;
 }
 
@@ -46,6 +50,13 @@ macro_rules! vertex {
                 Ok(slot)
             }
 
+            fn attrib_types() -> Vec<::gl_api::shader::reflect::GlslType> {
+                #[allow(unused_mut)]
+                let mut types = Vec::new();
+                $(types.extend(<$attrib_type as ::gl_api::layout::VertexAttribute>::attrib_types());)*
+                types
+            }
+
             // fn num_attrs() -> usize {
             //     let mut num = 0;
             //     $(num += <$attrib_type as VertexAttribute>::num_attrs();)*
@@ -85,6 +96,11 @@ macro_rules! layout_simple {
                 }
             }
 
+            // Whatever the type is, it gets converted to floats.
+            fn attrib_types() -> Vec<GlslType> {
+                vec![GlslType::vector(GlslType::Float, $amount)]
+            }
+
             const NUM_ATTRS: usize = 1;
         }
     };
@@ -106,6 +122,14 @@ macro_rules! layout_simple {
                 }
             }
 
+            fn attrib_types() -> Vec<GlslType> {
+                let scalar = match ::gl::$gl_type {
+                    ::gl::UNSIGNED_BYTE | ::gl::UNSIGNED_SHORT | ::gl::UNSIGNED_INT => GlslType::UInt,
+                    _ => GlslType::Int,
+                };
+                vec![GlslType::vector(scalar, $amount)]
+            }
+
             const NUM_ATTRS: usize = 1;
         }
     }
@@ -113,6 +137,7 @@ macro_rules! layout_simple {
 
 unsafe impl VertexAttribute for () {
     fn define_attribs(_slot: u32, _offset: u32) -> GlResult<u32> { Ok(0) }
+    fn attrib_types() -> Vec<GlslType> { Vec::new() }
     const NUM_ATTRS: usize = 0;
 }
 
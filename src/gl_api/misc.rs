@@ -1,4 +1,6 @@
 use gl;
+use gl::types::*;
+use std::ops::BitOr;
 
 #[allow(dead_code)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
@@ -14,6 +16,12 @@ pub fn polygon_mode(mode: PolygonMode) {
     unsafe { gl::PolygonMode(gl::FRONT_AND_BACK, mode as u32); }
 }
 
+/// Sets the area of the current draw framebuffer that gets drawn to.
+pub fn viewport(width: u32, height: u32) {
+    // Can only fail if the size is negative, which it can't be.
+    unsafe { gl::Viewport(0, 0, width as i32, height as i32); }
+}
+
 pub enum ClearMode {
     Color(f32, f32, f32, f32),
     Depth(f64),
@@ -35,3 +43,39 @@ pub fn clear(mode: ClearMode) {
     }
 }
 
+
+/// Kinds of memory access that have to wait for earlier shader writes to
+/// finish. Combine them with `|`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Barrier(GLbitfield);
+
+impl Barrier {
+    pub const VERTEX_ATTRIB_ARRAY: Barrier = Barrier(gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT);
+    pub const ELEMENT_ARRAY: Barrier = Barrier(gl::ELEMENT_ARRAY_BARRIER_BIT);
+    pub const UNIFORM: Barrier = Barrier(gl::UNIFORM_BARRIER_BIT);
+    pub const TEXTURE_FETCH: Barrier = Barrier(gl::TEXTURE_FETCH_BARRIER_BIT);
+    pub const SHADER_IMAGE_ACCESS: Barrier = Barrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    pub const COMMAND: Barrier = Barrier(gl::COMMAND_BARRIER_BIT);
+    pub const PIXEL_BUFFER: Barrier = Barrier(gl::PIXEL_BUFFER_BARRIER_BIT);
+    pub const TEXTURE_UPDATE: Barrier = Barrier(gl::TEXTURE_UPDATE_BARRIER_BIT);
+    pub const BUFFER_UPDATE: Barrier = Barrier(gl::BUFFER_UPDATE_BARRIER_BIT);
+    pub const FRAMEBUFFER: Barrier = Barrier(gl::FRAMEBUFFER_BARRIER_BIT);
+    pub const TRANSFORM_FEEDBACK: Barrier = Barrier(gl::TRANSFORM_FEEDBACK_BARRIER_BIT);
+    pub const ATOMIC_COUNTER: Barrier = Barrier(gl::ATOMIC_COUNTER_BARRIER_BIT);
+    pub const SHADER_STORAGE: Barrier = Barrier(gl::SHADER_STORAGE_BARRIER_BIT);
+    pub const ALL: Barrier = Barrier(gl::ALL_BARRIER_BITS);
+}
+
+impl BitOr for Barrier {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Barrier(self.0 | rhs.0)
+    }
+}
+
+/// Makes sure shader writes (from compute shaders, say) are visible to the
+/// kinds of access in `barriers` from here on.
+pub fn memory_barrier(barriers: Barrier) {
+    // Can only fail for invalid bits, and `Barrier` only has valid ones.
+    unsafe { gl::MemoryBarrier(barriers.0); }
+}
@@ -2,9 +2,15 @@
 pub mod error;
 #[macro_use]
 pub mod layout;
+#[macro_use]
+pub mod block;
 
 pub mod buffer;
+pub mod debug;
+pub mod framebuffer;
 pub mod misc;
+pub mod render;
+#[macro_use]
 pub mod shader;
 pub mod texture;
 pub mod uniform;
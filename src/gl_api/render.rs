@@ -1,13 +1,38 @@
+use gl;
+use gl_api::error::GlResult;
+use gl_api::framebuffer::{Framebuffer, FramebufferTarget};
+use gl_api::layout::VertexAttribute;
+use gl_api::shader::program::Program;
+use gl_api::vertex_array::VertexArray;
+
+/// Somewhere to draw to.
+#[derive(Debug)]
 pub enum RenderTarget {
+    /// The window.
     Default,
-    // Offscreen(Framebuffer)
+    Offscreen(Framebuffer),
 }
 
-pub struct RenderData {
-    
+impl RenderTarget {
+    pub fn bind(&self) -> GlResult<()> {
+        match *self {
+            RenderTarget::Default => Framebuffer::bind_default(FramebufferTarget::Draw),
+            RenderTarget::Offscreen(ref framebuffer) => framebuffer.bind(FramebufferTarget::Draw),
+        }
+    }
+
+    pub fn framebuffer(&self) -> Option<&Framebuffer> {
+        match *self {
+            RenderTarget::Default => None,
+            RenderTarget::Offscreen(ref framebuffer) => Some(framebuffer),
+        }
+    }
 }
 
-pub fn draw(buffer: VertexArray<V>, program: Program<V, E>, target: RenderTarget) {
-    buffer.bind();
-    program.bind();
+/// Draws `vertices` vertices worth of triangles from `vao` into `target`.
+pub fn draw<V: VertexAttribute, E>(vao: &VertexArray, program: &Program<V, E>, target: &RenderTarget, vertices: usize) -> GlResult<()> {
+    target.bind()?;
+    vao.bind()?;
+    program.bind()?;
+    unsafe { gl_call!(DrawArrays(gl::TRIANGLES, 0, vertices as i32)) }
 }
@@ -0,0 +1,118 @@
+use gl;
+use gl::types::*;
+use gl_api::shader::shader::ShaderType;
+use std::collections::hash_map::DefaultHasher;
+use std::ffi::CStr;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::PathBuf;
+
+// Goes at the start of every cached binary, so we don't try to hand GL
+// something that isn't one.
+const MAGIC: &[u8; 8] = b"BIRBPROG";
+
+/// Keeps linked programs around on disk, so they don't have to be compiled
+/// again next time. Binaries are keyed by the sources that went into them and
+/// by the driver, since drivers only accept binaries that they made.
+#[derive(Clone, Debug)]
+pub struct ProgramCache {
+    dir: PathBuf,
+}
+
+impl ProgramCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        ProgramCache { dir: dir.into() }
+    }
+
+    crate fn key(&self, sources: &[(ShaderType, &[u8])]) -> u64 {
+        key(&driver(), sources)
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.bin", key))
+    }
+
+    /// The binary format and the binary itself, if there is one.
+    crate fn load(&self, key: u64) -> Option<(GLenum, Vec<u8>)> {
+        decode(&fs::read(self.path(key)).ok()?)
+    }
+
+    crate fn store(&self, key: u64, format: GLenum, binary: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(key), encode(format, binary))
+    }
+
+    /// Throws out a binary that the driver didn't like.
+    crate fn remove(&self, key: u64) {
+        let _ = fs::remove_file(self.path(key));
+    }
+}
+
+// The vendor, renderer and version strings. Any of them changing probably
+// means the old binaries won't work anymore.
+fn driver() -> Vec<String> {
+    [gl::VENDOR, gl::RENDERER, gl::VERSION].iter()
+        .map(|&name| unsafe {
+            // UNWRAP: these are all valid names for `glGetString`
+            let string = gl_call!(GetString(name)).unwrap();
+            if string.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr(string as *const _).to_string_lossy().into_owned()
+            }
+        })
+        .collect()
+}
+
+// `DefaultHasher` isn't guaranteed to hash the same between Rust versions, but
+// the worst that can happen is that everything gets compiled again.
+fn key(driver: &[String], sources: &[(ShaderType, &[u8])]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    driver.hash(&mut hasher);
+    sources.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn encode(format: GLenum, binary: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(MAGIC.len() + 4 + binary.len());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&[format as u8, (format >> 8) as u8, (format >> 16) as u8, (format >> 24) as u8]);
+    data.extend_from_slice(binary);
+    data
+}
+
+fn decode(data: &[u8]) -> Option<(GLenum, Vec<u8>)> {
+    if data.len() < MAGIC.len() + 4 || &data[..MAGIC.len()] != MAGIC {
+        return None;
+    }
+    let format = &data[MAGIC.len()..MAGIC.len() + 4];
+    let format = format[0] as GLenum | (format[1] as GLenum) << 8 | (format[2] as GLenum) << 16 | (format[3] as GLenum) << 24;
+    Some((format, data[MAGIC.len() + 4..].to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_depend_on_everything() {
+        let driver = vec!["Vendor".to_owned(), "Renderer".to_owned(), "4.3".to_owned()];
+        let sources: &[(ShaderType, &[u8])] = &[(ShaderType::Vertex, b"void main() {}"), (ShaderType::Fragment, b"void main() {}")];
+        let base = key(&driver, sources);
+        assert_eq!(base, key(&driver, sources));
+
+        let swapped: &[(ShaderType, &[u8])] = &[(ShaderType::Fragment, b"void main() {}"), (ShaderType::Vertex, b"void main() {}")];
+        assert_ne!(base, key(&driver, swapped));
+        let updated = vec!["Vendor".to_owned(), "Renderer".to_owned(), "4.3 (new driver)".to_owned()];
+        assert_ne!(base, key(&updated, sources));
+    }
+
+    #[test]
+    fn binaries_round_trip() {
+        let data = encode(0x8741, &[1, 2, 3]);
+        assert_eq!(decode(&data), Some((0x8741, vec![1, 2, 3])));
+        assert_eq!(decode(&data[..6]), None);
+        assert_eq!(decode(b"not a program binary"), None);
+    }
+}
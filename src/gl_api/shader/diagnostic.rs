@@ -0,0 +1,271 @@
+use gl_api::shader::shader::ShaderType;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+// How many lines to show on either side of the one with the problem.
+const CONTEXT_LINES: usize = 2;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    /// Anything else the driver felt like saying.
+    Note,
+}
+
+impl Severity {
+    fn from_kind(kind: &str) -> Self {
+        let kind = kind.to_lowercase();
+        if kind.contains("error") {
+            Severity::Error
+        } else if kind.contains("warning") {
+            Severity::Warning
+        } else {
+            Severity::Note
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        })
+    }
+}
+
+/// One message out of a shader info log.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// The source string number, which is an index into the files that went
+    /// into the shader. See `Preprocessed::files`.
+    pub source: Option<usize>,
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// The lines around `line`, along with their line numbers.
+    pub context: Vec<(usize, String)>,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, message: &str) -> Self {
+        Diagnostic {
+            severity,
+            message: message.trim().into(),
+            source: None,
+            file: None,
+            line: None,
+            column: None,
+            context: Vec::new(),
+        }
+    }
+
+    fn at(mut self, source: usize, line: usize, column: Option<usize>) -> Self {
+        self.source = Some(source);
+        self.line = Some(line);
+        self.column = column;
+        self
+    }
+
+    /// Grabs the lines around where the problem is out of `text`, which should
+    /// be the file it's in.
+    pub fn with_context(mut self, text: &str) -> Self {
+        if let Some(line) = self.line {
+            let first = line.saturating_sub(CONTEXT_LINES).max(1);
+            self.context = text.lines().enumerate()
+                .map(|(i, text)| (i + 1, text.to_owned()))
+                .skip(first - 1)
+                .take(line + CONTEXT_LINES + 1 - first)
+                .collect();
+        }
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}: {}", self.severity, self.message)?;
+        let line = match self.line {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+
+        match (&self.file, self.source) {
+            (&Some(ref file), _) => write!(f, "  --> {}:{}", file.display(), line)?,
+            (&None, Some(source)) => write!(f, "  --> <source {}>:{}", source, line)?,
+            (&None, None) => write!(f, "  --> {}", line)?,
+        }
+        match self.column {
+            Some(column) => writeln!(f, ":{}", column)?,
+            None => writeln!(f)?,
+        }
+
+        let width = self.context.last().map(|&(number, _)| number.to_string().len()).unwrap_or(1);
+        for &(number, ref text) in &self.context {
+            let row = format!("{:>width$} | {}", number, text, width = width);
+            writeln!(f, "{}", row.trim_right())?;
+            if number == line {
+                if let Some(column) = self.column {
+                    writeln!(f, "{:>width$} | {:>column$}", "", "^", width = width, column = column)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Strips `prefix` off the start of `text`.
+fn expect<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    if text.starts_with(prefix) { Some(&text[prefix.len()..]) } else { None }
+}
+
+// Reads a number off the start of `text`.
+fn number(text: &str) -> Option<(usize, &str)> {
+    let end = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    Some((text[..end].parse().ok()?, &text[end..]))
+}
+
+// `kind: message`, where the kind is something like `error` or `error C1008`.
+fn kind_and_message(text: &str) -> (Severity, &str) {
+    match text.find(':') {
+        Some(split) => (Severity::from_kind(&text[..split]), &text[split + 1..]),
+        None => (Severity::from_kind(text), text),
+    }
+}
+
+// Mesa: `0:12(5): error: message`
+fn parse_mesa(line: &str) -> Option<Diagnostic> {
+    let (source, rest) = number(line)?;
+    let (row, rest) = number(expect(rest, ":")?)?;
+    let (column, rest) = number(expect(rest, "(")?)?;
+    let (severity, message) = kind_and_message(expect(rest, "):")?);
+    Some(Diagnostic::new(severity, message).at(source, row, Some(column)))
+}
+
+// NVIDIA: `0(12) : error C1008: message`
+fn parse_nvidia(line: &str) -> Option<Diagnostic> {
+    let (source, rest) = number(line)?;
+    let (row, rest) = number(expect(rest, "(")?)?;
+    let (severity, message) = kind_and_message(expect(rest, ") :")?);
+    Some(Diagnostic::new(severity, message).at(source, row, None))
+}
+
+// AMD, and a few others: `ERROR: 0:12: message`
+fn parse_amd(line: &str) -> Option<Diagnostic> {
+    let split = line.find(": ")?;
+    let severity = Severity::from_kind(&line[..split]);
+    let rest = &line[split + 2..];
+    let (source, rest) = number(rest)?;
+    let (row, rest) = number(expect(rest, ":")?)?;
+    let message = expect(rest, ":")?;
+    Some(Diagnostic::new(severity, message).at(source, row, None))
+}
+
+/// Picks apart an info log. Lines that don't look like any format we know
+/// about are kept as they are, without a location.
+pub fn parse_log(log: &str) -> Vec<Diagnostic> {
+    log.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            parse_mesa(line)
+                .or_else(|| parse_nvidia(line))
+                .or_else(|| parse_amd(line))
+                .unwrap_or_else(|| Diagnostic::new(Severity::from_kind(line), line))
+        })
+        .collect()
+}
+
+/// Why a shader didn't compile.
+#[derive(Clone, Debug)]
+pub struct CompileError {
+    pub shader_type: ShaderType,
+    pub diagnostics: Vec<Diagnostic>,
+    /// The info log, exactly as the driver gave it to us.
+    pub log: String,
+}
+
+impl CompileError {
+    /// `files` are the files that went into the shader, in source string
+    /// order. They get read again to show where each problem is.
+    pub fn new(shader_type: ShaderType, log: String, files: &[PathBuf]) -> Self {
+        let diagnostics = parse_log(&log).into_iter()
+            .map(|diagnostic| match diagnostic.source.and_then(|source| files.get(source)) {
+                Some(file) => {
+                    let diagnostic = match fs::read_to_string(file) {
+                        Ok(text) => diagnostic.with_context(&text),
+                        Err(_) => diagnostic,
+                    };
+                    Diagnostic { file: Some(file.clone()), ..diagnostic }
+                }
+                None => diagnostic,
+            })
+            .collect();
+        CompileError { shader_type, diagnostics, log }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:?} shader failed to compile", self.shader_type)?;
+        if self.diagnostics.is_empty() {
+            return writeln!(f, "(the info log was empty)");
+        }
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for CompileError {
+    fn description(&self) -> &str {
+        "a shader didn't compile"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_driver_logs() {
+        let mesa = parse_log("0:12(5): error: `foo' undeclared\n1:3(10): warning: unused variable\n");
+        assert_eq!(mesa.len(), 2);
+        assert_eq!((mesa[0].source, mesa[0].line, mesa[0].column), (Some(0), Some(12), Some(5)));
+        assert_eq!((mesa[0].severity, &mesa[0].message[..]), (Severity::Error, "`foo' undeclared"));
+        assert_eq!((mesa[1].source, mesa[1].severity), (Some(1), Severity::Warning));
+
+        let nvidia = parse_log("0(7) : error C1008: undefined variable \"foo\"");
+        assert_eq!((nvidia[0].source, nvidia[0].line, nvidia[0].column), (Some(0), Some(7), None));
+        assert_eq!((nvidia[0].severity, &nvidia[0].message[..]), (Severity::Error, "undefined variable \"foo\""));
+
+        let amd = parse_log("ERROR: 0:3: 'foo' : undeclared identifier\nERROR: 1 compilation errors.  No code generated.");
+        assert_eq!((amd[0].source, amd[0].line, amd[0].severity), (Some(0), Some(3), Severity::Error));
+        assert_eq!(amd[0].message, "'foo' : undeclared identifier");
+        assert_eq!((amd[1].line, amd[1].severity), (None, Severity::Error));
+    }
+
+    #[test]
+    fn shows_surrounding_lines() {
+        let text = "#version 430\n\nvoid main() {\n    foo = 1.0;\n}\n";
+        let diagnostic = parse_log("0:4(5): error: `foo' undeclared").remove(0).with_context(text);
+        let diagnostic = Diagnostic { file: Some("res/test.glslf".into()), ..diagnostic };
+
+        assert_eq!(diagnostic.to_string(), "\
+error: `foo' undeclared
+  --> res/test.glslf:4:5
+2 |
+3 | void main() {
+4 |     foo = 1.0;
+  |     ^
+5 | }
+");
+    }
+}
@@ -1,7 +1,14 @@
+use std::error;
+use std::fmt;
 use std::io;
 use std::path::Path;
 
+pub mod binary;
+pub mod diagnostic;
+#[macro_use]
 pub mod program;
+pub mod preprocess;
+pub mod reflect;
 pub mod shader;
 
 use self::program::*;
@@ -11,7 +18,37 @@ use self::shader::*;
 pub enum PipelineError {
     Shader(ShaderError),
     Io(io::Error),
-    ProgramCreation,
+    Program(ProgramError),
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PipelineError::Shader(ref err) => write!(f, "{}", err),
+            PipelineError::Io(ref err) => write!(f, "{}", err),
+            PipelineError::Program(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for PipelineError {
+    fn description(&self) -> &str {
+        "couldn't build a shader pipeline"
+    }
+
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            PipelineError::Shader(ref err) => error::Error::source(err),
+            PipelineError::Io(ref err) => error::Error::source(err),
+            PipelineError::Program(ref err) => error::Error::source(err),
+        }
+    }
+}
+
+impl From<ProgramError> for PipelineError {
+    fn from(err: ProgramError) -> Self {
+        PipelineError::Program(err)
+    }
 }
 
 impl From<ShaderError> for PipelineError {
@@ -30,15 +67,15 @@ pub fn simple_pipeline<P1: AsRef<Path>, P2: AsRef<Path>>(
     vert: P1,
     frag: P2,
 ) -> Result<RawLinkedProgram, PipelineError> {
-    let program = RawProgram::new().ok_or(PipelineError::ProgramCreation)?;
-    let vert_shader = Shader::new(ShaderType::Vertex)?;
-    let frag_shader = Shader::new(ShaderType::Fragment)?;
+    let program = RawProgram::new()?;
+    let mut vert_shader = Shader::new(ShaderType::Vertex)?;
+    let mut frag_shader = Shader::new(ShaderType::Fragment)?;
 
     vert_shader.source_from_file(vert)?;
     frag_shader.source_from_file(frag)?;
 
-    program.attach_shader(vert_shader.compile()?);
-    program.attach_shader(frag_shader.compile()?);
+    program.attach_shader(vert_shader.compile()?).map_err(ProgramError::from)?;
+    program.attach_shader(frag_shader.compile()?).map_err(ProgramError::from)?;
 
-    Ok(program.link().unwrap())
+    Ok(program.link()?)
 }
@@ -0,0 +1,325 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::fmt::{self, Write};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// Where `#include`s get looked up.
+pub const INCLUDE_ROOT: &str = "res";
+
+/// `#define`s that get added to the top of a shader. They're kept sorted so
+/// that the same set of defines always compares (and hashes) the same.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Defines(BTreeMap<String, String>);
+
+impl Defines {
+    pub fn new() -> Self {
+        Defines(BTreeMap::new())
+    }
+
+    pub fn define<N: Into<String>, V: ToString>(mut self, name: N, value: V) -> Self {
+        self.0.insert(name.into(), value.to_string());
+        self
+    }
+
+    /// A define without a value, for `#ifdef`.
+    pub fn flag<N: Into<String>>(mut self, name: N) -> Self {
+        self.0.insert(name.into(), String::new());
+        self
+    }
+
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+        self.0.iter().map(|(name, value)| (&name[..], &value[..]))
+    }
+}
+
+#[derive(Debug)]
+pub enum PreprocessError {
+    Io(PathBuf, io::Error),
+    /// An `#include` that wasn't followed by a path in quotes.
+    BadInclude { file: PathBuf, line: usize },
+    /// A file ended up including itself. Goes from the outermost file to the
+    /// one that got included again.
+    IncludeCycle(Vec<PathBuf>),
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PreprocessError::Io(ref path, ref err) => write!(f, "couldn't read {}: {}", path.display(), err),
+            PreprocessError::BadInclude { ref file, line } => write!(f, "{}:{}: `#include` needs a path in quotes", file.display(), line),
+            PreprocessError::IncludeCycle(ref cycle) => {
+                write!(f, "a file ended up including itself: ")?;
+                for (i, path) in cycle.iter().enumerate() {
+                    write!(f, "{}{}", if i == 0 { "" } else { " -> " }, path.display())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl error::Error for PreprocessError {
+    fn description(&self) -> &str {
+        "couldn't preprocess a shader"
+    }
+
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            PreprocessError::Io(_, ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// A shader with all of its includes pasted in.
+#[derive(Clone, Debug)]
+pub struct Preprocessed {
+    pub source: String,
+    /// Every file that went into the source. The `#line` directives use the
+    /// index in here as the source string number, so the main file is 0.
+    pub files: Vec<PathBuf>,
+}
+
+pub struct Preprocessor<'d> {
+    root: PathBuf,
+    defines: &'d Defines,
+}
+
+impl<'d> Preprocessor<'d> {
+    pub fn new<P: Into<PathBuf>>(root: P, defines: &'d Defines) -> Self {
+        Preprocessor { root: root.into(), defines }
+    }
+
+    pub fn process<P: AsRef<Path>>(&self, path: P) -> Result<Preprocessed, PreprocessError> {
+        let mut out = Preprocessed { source: String::new(), files: Vec::new() };
+        self.expand(path.as_ref(), &mut out, &mut Vec::new())?;
+        Ok(out)
+    }
+
+    // `#line` sets the number of the line right after it, as of GLSL 3.30.
+    // Before that it was the number of the directive itself, but nothing here
+    // uses versions that old.
+    fn expand(&self, path: &Path, out: &mut Preprocessed, stack: &mut Vec<PathBuf>) -> Result<(), PreprocessError> {
+        let path = &normalize(path);
+        if stack.iter().any(|included| included == path) {
+            let mut cycle = stack.clone();
+            cycle.push(path.to_owned());
+            return Err(PreprocessError::IncludeCycle(cycle));
+        }
+
+        let text = read_file(path).map_err(|err| PreprocessError::Io(path.to_owned(), err))?;
+        let index = out.files.len();
+        out.files.push(path.to_owned());
+        stack.push(path.to_owned());
+
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line)).peekable();
+        if stack.len() == 1 {
+            // `#version` has to come before anything else, so the defines go
+            // right after it if there is one.
+            if let Some(&(_, first)) = lines.peek() {
+                if first.trim_left().starts_with("#version") {
+                    out.source.push_str(first);
+                    out.source.push('\n');
+                    lines.next();
+                }
+            }
+            if !self.defines.0.is_empty() {
+                for (name, value) in self.defines.iter() {
+                    let _ = match value {
+                        "" => writeln!(out.source, "#define {}", name),
+                        value => writeln!(out.source, "#define {} {}", name, value),
+                    };
+                }
+                let _ = writeln!(out.source, "#line {} {}", lines.peek().map(|&(number, _)| number).unwrap_or(1), index);
+            }
+        } else {
+            let _ = writeln!(out.source, "#line 1 {}", index);
+        }
+
+        for (number, line) in lines {
+            match include_path(line) {
+                Some(Some(include)) => {
+                    self.expand(&self.root.join(include), out, stack)?;
+                    let _ = writeln!(out.source, "#line {} {}", number + 1, index);
+                }
+                Some(None) => return Err(PreprocessError::BadInclude { file: path.to_owned(), line: number }),
+                None => {
+                    out.source.push_str(line);
+                    out.source.push('\n');
+                }
+            }
+        }
+
+        stack.pop();
+        Ok(())
+    }
+}
+
+// Takes out `.` and `..` without touching the file system, so that a file
+// included as `./a.glsl` or `include/../a.glsl` is still recognized as `a.glsl`
+// when looking for cycles.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => { normalized.pop(); }
+                // Can't go any higher than the root
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => (),
+                _ => normalized.push(".."),
+            },
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+fn read_file(path: &Path) -> io::Result<String> {
+    let mut buf = String::new();
+    File::open(path)?.read_to_string(&mut buf)?;
+    Ok(buf)
+}
+
+// `None` if the line isn't an include at all, and `Some(None)` if it is but
+// it's missing the path.
+fn include_path(line: &str) -> Option<Option<&str>> {
+    let line = line.trim();
+    if !line.starts_with('#') || !line[1..].trim_left().starts_with("include") {
+        return None;
+    }
+
+    let rest = line[1..].trim_left()["include".len()..].trim();
+    if rest.len() >= 2 && rest.starts_with('"') && rest.ends_with('"') {
+        Some(Some(&rest[1..rest.len() - 1]))
+    } else {
+        Some(None)
+    }
+}
+
+/// Holds onto things built from the same shaders with different defines, so
+/// each variant only gets compiled once.
+pub struct VariantCache<T> {
+    variants: HashMap<Defines, T>,
+}
+
+impl<T> VariantCache<T> {
+    pub fn new() -> Self {
+        VariantCache { variants: HashMap::new() }
+    }
+
+    pub fn get_or_build<E, F: FnOnce(&Defines) -> Result<T, E>>(&mut self, defines: &Defines, build: F) -> Result<&mut T, E> {
+        if !self.variants.contains_key(defines) {
+            let variant = build(defines)?;
+            self.variants.insert(defines.clone(), variant);
+        }
+        // UNWRAP: it was either already there or we just put it there
+        Ok(self.variants.get_mut(defines).unwrap())
+    }
+
+    pub fn get(&self, defines: &Defines) -> Option<&T> {
+        self.variants.get(defines)
+    }
+
+    pub fn get_mut(&mut self, defines: &Defines) -> Option<&mut T> {
+        self.variants.get_mut(defines)
+    }
+
+    /// Puts in a variant that was built some other way, replacing whatever was
+    /// there.
+    pub fn insert(&mut self, defines: Defines, variant: T) {
+        self.variants.insert(defines, variant);
+    }
+
+    /// Throws out every variant, for when the shaders themselves change.
+    pub fn clear(&mut self) {
+        self.variants.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    // Writes out `files` into a fresh directory, which is used as the include
+    // root.
+    fn setup(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = env::temp_dir().join(format!("birblike-preprocess-{}", name));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        for &(path, text) in files {
+            fs::write(root.join(path), text).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn includes_and_defines() {
+        let root = setup("includes", &[
+            ("main.glsl", "#version 430\n#include \"common.glsl\"\nvoid main() {}\n"),
+            ("common.glsl", "float common() { return 1.0; }\n"),
+        ]);
+        let defines = Defines::new().define("RADIUS", 4).flag("FANCY");
+        let processed = Preprocessor::new(&root, &defines).process(root.join("main.glsl")).unwrap();
+
+        assert_eq!(processed.source, "\
+#version 430
+#define FANCY
+#define RADIUS 4
+#line 2 0
+#line 1 1
+float common() { return 1.0; }
+#line 3 0
+void main() {}
+");
+        assert_eq!(processed.files, vec![root.join("main.glsl"), root.join("common.glsl")]);
+    }
+
+    #[test]
+    fn include_errors() {
+        let root = setup("errors", &[
+            ("a.glsl", "#include \"b.glsl\"\n"),
+            ("b.glsl", "\n  #  include \"a.glsl\"\n"),
+            ("bad.glsl", "#include common.glsl\n"),
+            ("c.glsl", "#include \"./d.glsl\"\n"),
+            ("d.glsl", "#include \"missing/../c.glsl\"\n"),
+        ]);
+        let defines = Defines::new();
+        let preprocessor = Preprocessor::new(&root, &defines);
+
+        match preprocessor.process(root.join("a.glsl")) {
+            Err(PreprocessError::IncludeCycle(cycle)) => assert_eq!(cycle.len(), 3),
+            other => panic!("expected a cycle, got {:?}", other),
+        }
+        match preprocessor.process(root.join("c.glsl")) {
+            Err(PreprocessError::IncludeCycle(cycle)) => assert_eq!(cycle, vec![root.join("c.glsl"), root.join("d.glsl"), root.join("c.glsl")]),
+            other => panic!("expected a cycle, got {:?}", other),
+        }
+        match preprocessor.process(root.join("bad.glsl")) {
+            Err(PreprocessError::BadInclude { line: 1, .. }) => (),
+            other => panic!("expected a bad include, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn variants_build_once() {
+        let mut cache = VariantCache::new();
+        let mut builds = 0;
+        let fancy = Defines::new().flag("FANCY");
+        for defines in &[Defines::new(), fancy.clone(), Defines::new().flag("FANCY")] {
+            let variant = cache.get_or_build(defines, |defines| -> Result<_, ()> {
+                builds += 1;
+                Ok(defines.iter().count())
+            }).unwrap();
+            assert_eq!(*variant, defines.iter().count());
+        }
+        assert_eq!(builds, 2);
+
+        cache.clear();
+        assert_eq!(cache.get(&fancy), None);
+    }
+}
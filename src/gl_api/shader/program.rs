@@ -1,12 +1,18 @@
-use gl_api::buffer::ShaderStorageBuffer;
+use gl_api::block::{Std140Block, Std430, StorageBuffer, UniformBlock};
+use gl_api::buffer::{DispatchIndirectBuffer, DispatchIndirectCommand, ShaderStorageBuffer};
 use gl_api::uniform::BoundUniform;
+use gl_api::shader::binary::ProgramCache;
+use gl_api::shader::reflect::{Block, GlslType, ProgramInterface};
 use gl_api::shader::shader::ShaderError;
 use gl_api::shader::shader::ShaderResult;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use gl_api::shader::shader::ShaderType;
+use gl_api::debug;
 use gl_api::error::GlResult;
 use gl_api::shader::shader::Shader;
 use gl_api::layout::VertexAttribute;
+use std::error;
+use std::fmt;
 use std::marker::PhantomData;
 use super::super::error::GlError;
 use super::shader::CompiledShader;
@@ -32,70 +38,251 @@ impl<A> ::std::ops::DerefMut for StorageBindPoint<A> {
 
 pub struct UniformBlockBuilder<'p> {
     program: &'p RawLinkedProgram,
+    interface: ProgramInterface,
     buffer_bind_point: u32,
+    uniform_bind_point: u32,
 }
 
 #[derive(Clone, Debug)]
 pub enum UniformError {
     NameError(String),
+    /// A member of a block is somewhere other than where the Rust side of it
+    /// thinks it is.
+    LayoutMismatch { block: String, member: String, expected: usize, actual: usize },
+    /// The Rust side of a block has a member that the shader doesn't.
+    MissingMember { block: String, member: String },
+    SizeMismatch { block: String, expected: usize, actual: usize },
+    /// The elements of a storage block's array are a different distance apart
+    /// than the Rust side of it expects.
+    StrideMismatch { block: String, expected: usize, actual: usize },
+    /// The shader declared a uniform as `actual`, but the Rust side of it sets
+    /// an `expected`.
+    TypeMismatch { name: String, expected: GlslType, actual: GlslType },
+    Gl(GlError),
+}
+
+impl From<GlError> for UniformError {
+    fn from(err: GlError) -> Self { UniformError::Gl(err) }
 }
 
 impl<'p> UniformBlockBuilder<'p> {
     pub fn uniform<U: BoundUniform>(&self, name: &str) -> Result<Uniform<U>, UniformError> {
-        self.program.0.bind();
+        self.program.0.bind()?;
+        let uniform = self.interface.uniform(name).ok_or_else(|| UniformError::NameError(name.into()))?;
+        if !U::glsl_type().can_set(uniform.ty) {
+            return Err(UniformError::TypeMismatch { name: name.into(), expected: U::glsl_type(), actual: uniform.ty });
+        }
+        Ok(Uniform::new(uniform.location))
+    }
+
+    /// Gets a storage block holding a single std430 array of `T`, like
+    /// `buffer name { T items[]; };`, checking that the layouts match.
+    pub fn shader_storage<T: Std430>(&mut self, name: &str) -> Result<StorageBuffer<T>, UniformError> {
+        self.program.0.bind()?;
+        let block = self.interface.storage_block(name).ok_or_else(|| UniformError::NameError(name.into()))?;
+        validate_storage::<T>(block)?;
+
+        let bind_point = self.buffer_bind_point;
         unsafe {
-            use std::ffi::CString;
-            let c_string = CString::new(name).unwrap();
-            // UNWRAP: program ID is valid, and the program has been successfully linked
-            let location = gl_call!(GetUniformLocation(self.program.0.id, c_string.as_ptr())).unwrap();
-            if location == -1 {
-                Err(UniformError::NameError(c_string.into_string().unwrap_or_default()))
-            } else {
-                Ok(Uniform::new(location))
-            }
+            gl_call!(ShaderStorageBlockBinding(self.program.0.id, block.index, bind_point))?;
         }
+        self.buffer_bind_point += 1;
+        Ok(StorageBuffer::new(bind_point)?)
     }
 
-    pub fn shader_storage<A: VertexAttribute>(&mut self, name: &str) -> Result<ShaderStorageBuffer<A>, UniformError> {
-        self.program.0.bind();
+    /// Gets a std140 uniform block, checking that `T` has the same layout as
+    /// the block in the shader.
+    pub fn uniform_block<T: Std140Block>(&mut self, name: &str) -> Result<UniformBlock<T>, UniformError> {
+        self.program.0.bind()?;
+        let block = self.interface.uniform_block(name).ok_or_else(|| UniformError::NameError(name.into()))?;
+        validate_block::<T>(block)?;
+
+        let bind_point = self.uniform_bind_point;
         unsafe {
-            use std::ffi::CString;
-            let c_string = CString::new(name).unwrap();
-            let bind_point = self.buffer_bind_point;
-            let ssbo = ShaderStorageBuffer::new(bind_point);
-            ssbo.bind();
-            let block_index = gl_call!(GetProgramResourceIndex(
-                self.program.0.id,
-                ::gl::SHADER_STORAGE_BLOCK,
-                c_string.as_ptr())
-            ).unwrap();
-            if block_index == gl::INVALID_INDEX {
-                // TODO: better error? This returns a useless empty string if
-                // the C string was invalid in some way.
-                Err(UniformError::NameError(c_string.into_string().unwrap_or_default()))
-            } else {
-                gl_call!(ShaderStorageBlockBinding(self.program.0.id, block_index, bind_point)).unwrap();
-                self.buffer_bind_point += 1;
-                Ok(ssbo)
+            gl_call!(UniformBlockBinding(self.program.0.id, block.index, bind_point))?;
+        }
+        self.uniform_bind_point += 1;
+        Ok(UniformBlock::new(bind_point)?)
+    }
+}
+
+/// Anything that can be a field of a `program_env!` struct.
+pub trait EnvField: Sized {
+    fn from_builder(builder: &mut UniformBlockBuilder, name: &str) -> Result<Self, UniformError>;
+}
+
+impl<U: BoundUniform> EnvField for Uniform<U> {
+    fn from_builder(builder: &mut UniformBlockBuilder, name: &str) -> Result<Self, UniformError> {
+        builder.uniform(name)
+    }
+}
+
+impl<T: Std430> EnvField for StorageBuffer<T> {
+    fn from_builder(builder: &mut UniformBlockBuilder, name: &str) -> Result<Self, UniformError> {
+        builder.shader_storage(name)
+    }
+}
+
+impl<T: Std140Block> EnvField for UniformBlock<T> {
+    fn from_builder(builder: &mut UniformBlockBuilder, name: &str) -> Result<Self, UniformError> {
+        builder.uniform_block(name)
+    }
+}
+
+/// For things the shader is allowed to not have, or that might get optimized
+/// out. Anything that's there still has to be the right type.
+impl<F: EnvField> EnvField for Option<F> {
+    fn from_builder(builder: &mut UniformBlockBuilder, name: &str) -> Result<Self, UniformError> {
+        match F::from_builder(builder, name) {
+            Ok(field) => Ok(Some(field)),
+            Err(UniformError::NameError(_)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Declares a program environment struct, along with a `from_builder` function
+/// that can be passed straight to `ProgramBuilder::build`. Each field is looked
+/// up by its name unless it has a `#[name = "..."]`, and `Option` fields are
+/// allowed to be missing from the shader.
+///
+/// ```ignore
+/// program_env! {
+///     env Uniforms {
+///         #[name = "u_texture"]
+///         texture: Uniform<Texture2D>,
+///         time: Option<Uniform<f32>>,
+///     }
+/// }
+/// ```
+macro_rules! program_env {
+    (env $name:ident {
+        $($(#[name = $glsl:expr])* $field:ident: $field_type:ty,)*
+    }) => {
+        pub struct $name {
+            $(pub $field: $field_type),*
+        }
+
+        impl $name {
+            #[allow(unused_mut, unused_variables)]
+            pub fn from_builder(mut builder: ::gl_api::shader::program::UniformBlockBuilder)
+                -> Result<Self, ::gl_api::shader::program::ProgramError>
+            {
+                Ok($name {
+                    $($field: <$field_type as ::gl_api::shader::program::EnvField>::from_builder(
+                        &mut builder,
+                        program_env!(@name $field $($glsl)*),
+                    )?,)*
+                })
             }
         }
+    };
+
+    (@name $field:ident) => { stringify!($field) };
+    (@name $field:ident $glsl:expr) => { $glsl };
+}
+
+// Members of a block with an instance name are reflected as `Block.member`.
+fn strip_block_name<'a>(block: &str, member: &'a str) -> &'a str {
+    if member.starts_with(block) && member[block.len()..].starts_with('.') {
+        &member[block.len() + 1..]
+    } else {
+        member
     }
 }
 
+// Checks that the members of a uniform block are where `T` puts them.
+fn validate_block<T: Std140Block>(block: &Block) -> Result<(), UniformError> {
+    if block.size != T::std140_size() {
+        return Err(UniformError::SizeMismatch { block: block.name.clone(), expected: T::std140_size(), actual: block.size });
+    }
+
+    let members = block.members.iter()
+        .map(|member| (strip_block_name(&block.name, &member.name), member.offset))
+        .collect::<Vec<_>>();
+    check_offsets(&block.name, &members, T::std140_offsets())
+}
+
+// Checks that the members of a storage block are where `T` puts them, and that
+// the array elements are spaced out the same.
+fn validate_storage<T: Std430>(block: &Block) -> Result<(), UniformError> {
+    let mut members = Vec::with_capacity(block.members.len());
+    for member in &block.members {
+        if member.top_level_stride != T::std430_stride() {
+            return Err(UniformError::StrideMismatch {
+                block: block.name.clone(), expected: T::std430_stride(), actual: member.top_level_stride,
+            });
+        }
+
+        // Members of an array of structs come back as `items[0].member`, and
+        // we only care about the `member` part.
+        let name = match member.name.find("].") {
+            Some(split) => &member.name[split + 2..],
+            None => "",
+        };
+        members.push((name, member.offset));
+    }
+
+    check_offsets(&block.name, &members, T::std430_offsets())
+}
+
+// Arrays come back as `member[0]`, and structs as each of their fields, so a
+// struct starts wherever its first field does.
+fn check_offsets(block: &str, members: &[(&str, usize)], expected: Vec<(&'static str, usize)>) -> Result<(), UniformError> {
+    for (member, expected) in expected {
+        let actual = members.iter()
+            .filter(|&&(reflected, _)| {
+                reflected == member
+                    || reflected.starts_with(&format!("{}[", member))
+                    || reflected.starts_with(&format!("{}.", member))
+            })
+            .map(|&(_, offset)| offset)
+            .min();
+
+        match actual {
+            Some(actual) if actual != expected => return Err(UniformError::LayoutMismatch {
+                block: block.into(), member: member.into(), expected, actual,
+            }),
+            Some(_) => (),
+            None => return Err(UniformError::MissingMember { block: block.into(), member: member.into() }),
+        }
+    }
+
+    Ok(())
+}
+
+// Vertex attributes get slots in the order that `I` lists them, so each input
+// of the vertex shader has to match whatever's in its location.
+fn validate_inputs<I: VertexAttribute>(interface: &ProgramInterface) -> Result<(), ProgramError> {
+    let supplied = I::attrib_types();
+    for input in interface.attributes.iter().filter(|input| !input.name.starts_with("gl_")) {
+        match supplied.get(input.location as usize) {
+            Some(&ty) if ty != input.ty => return Err(ProgramError::InputMismatch {
+                name: input.name.clone(), location: input.location as u32, expected: ty, actual: input.ty,
+            }),
+            Some(_) => (),
+            None => return Err(ProgramError::MissingInput { name: input.name.clone(), location: input.location as u32 }),
+        }
+    }
+    Ok(())
+}
+
 pub struct ProgramBuilder {
     program: RawProgram,
     vertex: Shader,
     fragment: Shader,
     geometry: Option<Shader>,
     tess: Option<(Shader, Shader)>,
+    cache: Option<ProgramCache>,
 }
 
 impl ProgramBuilder {
-    pub fn new(vertex: Shader, fragment: Shader) -> Option<Self> {
-        Some(ProgramBuilder {
+    pub fn new(vertex: Shader, fragment: Shader) -> Result<Self, ProgramError> {
+        Ok(ProgramBuilder {
             program: RawProgram::new()?, vertex, fragment,
             geometry: None,
             tess: None,
+            cache: None,
         })
     }
 
@@ -112,35 +299,89 @@ impl ProgramBuilder {
         self
     }
 
-    pub fn build<I, E, F: Fn(UniformBlockBuilder) -> Result<E, ProgramError>>(self, func: F) -> Result<Program<I, E>, ProgramError> {
-        // Attach all the shaders. Not sure if they have to be attached in order
-        // or not, so I'm going to assume for now that they do.
-        self.program.attach_shader(self.vertex.compile()?);
+    /// Saves the linked program in `cache`, and loads it from there instead of
+    /// compiling everything if it's been built before. `None` compiles
+    /// everything every time.
+    pub fn with_cache(mut self, cache: Option<&ProgramCache>) -> Self {
+        self.cache = cache.cloned();
+        self
+    }
+
+    pub fn build<I: VertexAttribute, E, F: Fn(UniformBlockBuilder) -> Result<E, ProgramError>>(self, func: F) -> Result<Program<I, E>, ProgramError> {
+        // Not sure if the shaders have to be attached in order or not, so I'm
+        // going to assume for now that they do.
+        let mut shaders = vec![self.vertex];
         if let Some((tess_control, tess_eval)) = self.tess {
-            self.program.attach_shader(tess_control.compile()?);
-            self.program.attach_shader(tess_eval.compile()?);
+            shaders.push(tess_control);
+            shaders.push(tess_eval);
         }
-        if let Some(geometry) = self.geometry {
-            self.program.attach_shader(geometry.compile()?);
-        }
-        self.program.attach_shader(self.fragment.compile()?);
-        // Link the program and build the user-defined uniform interface. We
-        // have to do it here in a closure because we can't access the linked
-        // program before this point, and we need the uniform interface for the
-        // program! We parameterize types on the function here because this is
-        // where the actual verification for the types happens.
-        // TODO: make sure input type is correct.
-        let raw = self.program.link()?;
-        let environment = func(UniformBlockBuilder { program: &raw, buffer_bind_point: 0 })?;
+        shaders.extend(self.geometry);
+        shaders.push(self.fragment);
+
+        let files = shaders.iter().flat_map(|shader| shader.files().iter().cloned()).collect();
+        let cached = self.cache.map(|cache| {
+            let sources = shaders.iter().map(|shader| (shader.shader_type, &shader.source[..])).collect::<Vec<_>>();
+            let key = cache.key(&sources);
+            (cache, key)
+        });
+
+        let program = match cached {
+            Some((ref cache, key)) => load_cached(self.program, cache, key),
+            None => Err(self.program),
+        };
+        let raw = match program {
+            Ok(raw) => raw,
+            Err(program) => {
+                if cached.is_some() {
+                    program.set_binary_retrievable()?;
+                }
+                for shader in shaders {
+                    program.attach_shader(shader.compile()?)?;
+                }
+                let raw = program.link()?;
+                if let Some((ref cache, key)) = cached {
+                    // Not being able to write the cache just means compiling
+                    // again next time, which isn't worth failing over.
+                    if let Some((format, binary)) = raw.binary() {
+                        let _ = cache.store(key, format, &binary);
+                    }
+                }
+                raw
+            }
+        };
+
+        // Build the user-defined uniform interface. We have to do it here in a
+        // closure because we can't access the linked program before this
+        // point, and we need the uniform interface for the program! We
+        // parameterize types on the function here because this is where the
+        // actual verification for the types happens.
+        let interface = ProgramInterface::query(raw.0.id)?;
+        validate_inputs::<I>(&interface)?;
+        let environment = func(UniformBlockBuilder { program: &raw, interface, buffer_bind_point: 0, uniform_bind_point: 0 })?;
         Ok(Program {
-            raw, environment, _marker: PhantomData,
+            raw, environment, files, _marker: PhantomData,
         })
     }
 }
 
+// Links `program` from whatever binary `cache` has for `key`, or hands it back
+// if there isn't one or the driver won't take it.
+fn load_cached(program: RawProgram, cache: &ProgramCache, key: u64) -> Result<RawLinkedProgram, RawProgram> {
+    let (format, binary) = match cache.load(key) {
+        Some(cached) => cached,
+        None => return Err(program),
+    };
+    program.link_binary(format, &binary).map_err(|program| {
+        // Probably from an older driver, so it's never going to work.
+        cache.remove(key);
+        program
+    })
+}
+
 pub struct Program<I, E> {
     raw: RawLinkedProgram,
     environment: E,
+    files: Vec<PathBuf>,
     _marker: PhantomData<I>,
 }
 
@@ -149,14 +390,124 @@ impl<In: VertexAttribute, Env> Program<In, Env> {
         &mut self.environment
     }
 
-    pub fn bind(&self) {
-        self.raw.0.bind();
+    /// Everything the shaders use, according to GL.
+    pub fn interface(&self) -> GlResult<ProgramInterface> {
+        ProgramInterface::query(self.raw.0.id)
+    }
+
+    /// Every file that went into the program's shaders.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Names the program in debug messages.
+    pub fn set_label(&self, label: &str) {
+        self.raw.0.set_label(label);
+    }
+
+    pub fn bind(&self) -> GlResult<()> {
+        self.raw.0.bind()
     }
 
     // TODO: Remove and lift to `Env`
     // pub fn 
 }
 
+pub struct ComputeProgramBuilder {
+    program: RawProgram,
+    compute: Shader,
+}
+
+impl ComputeProgramBuilder {
+    pub fn new(compute: Shader) -> Result<Self, ProgramError> {
+        if compute.shader_type != ShaderType::Compute {
+            return Err(ProgramError::WrongShaderType { expected: ShaderType::Compute, actual: compute.shader_type });
+        }
+        Ok(ComputeProgramBuilder { program: RawProgram::new()?, compute })
+    }
+
+    /// Works just like `ProgramBuilder::build`.
+    pub fn build<E, F: Fn(UniformBlockBuilder) -> Result<E, ProgramError>>(self, func: F) -> Result<ComputeProgram<E>, ProgramError> {
+        let files = self.compute.files().to_vec();
+        self.program.attach_shader(self.compute.compile()?)?;
+        let raw = self.program.link()?;
+
+        let mut work_group_size = [0; 3];
+        unsafe {
+            gl_call!(GetProgramiv((raw.0).id, gl::COMPUTE_WORK_GROUP_SIZE, work_group_size.as_mut_ptr()))?;
+        }
+
+        let interface = ProgramInterface::query(raw.0.id)?;
+        let environment = func(UniformBlockBuilder { program: &raw, interface, buffer_bind_point: 0, uniform_bind_point: 0 })?;
+        Ok(ComputeProgram {
+            raw,
+            environment,
+            files,
+            work_group_size: [work_group_size[0] as u32, work_group_size[1] as u32, work_group_size[2] as u32],
+        })
+    }
+}
+
+/// A program made of just a compute shader. Nothing it writes is guaranteed to
+/// be visible to anything that runs afterwards until there's been a
+/// `misc::memory_barrier`.
+pub struct ComputeProgram<E> {
+    raw: RawLinkedProgram,
+    environment: E,
+    files: Vec<PathBuf>,
+    work_group_size: [u32; 3],
+}
+
+impl<Env> ComputeProgram<Env> {
+    pub fn env_mut(&mut self) -> &mut Env {
+        &mut self.environment
+    }
+
+    pub fn interface(&self) -> GlResult<ProgramInterface> {
+        ProgramInterface::query(self.raw.0.id)
+    }
+
+    pub fn set_label(&self, label: &str) {
+        self.raw.0.set_label(label);
+    }
+
+    pub fn bind(&self) -> GlResult<()> {
+        self.raw.0.bind()
+    }
+
+    /// The `local_size` the shader was declared with.
+    pub fn work_group_size(&self) -> [u32; 3] {
+        self.work_group_size
+    }
+
+    /// Runs `x * y * z` work groups.
+    pub fn dispatch(&self, x: u32, y: u32, z: u32) -> GlResult<()> {
+        self.bind()?;
+        // Could fail if any of the counts are over GL_MAX_COMPUTE_WORK_GROUP_COUNT
+        unsafe { gl_call!(DispatchCompute(x, y, z)) }
+    }
+
+    /// Runs enough work groups that there's an invocation for every cell of a
+    /// `width * height * depth` grid. Invocations that end up off the edge of
+    /// the grid still run, so the shader should check its bounds.
+    pub fn dispatch_covering(&self, width: u32, height: u32, depth: u32) -> GlResult<()> {
+        let [gx, gy, gz] = self.work_group_size;
+        let groups = |n: u32, size: u32| (n + size - 1) / size;
+        self.dispatch(groups(width, gx), groups(height, gy), groups(depth, gz))
+    }
+
+    /// Runs however many work groups the command at `index` in `commands` says
+    /// to, so that a previous pass on the GPU can decide how much work to do.
+    pub fn dispatch_indirect(&self, commands: &DispatchIndirectBuffer, index: usize) -> GlResult<()> {
+        self.bind()?;
+        commands.bind()?;
+        let offset = index * ::std::mem::size_of::<DispatchIndirectCommand>();
+        // GL checks that the command is inside the buffer, and fails with
+        // GL_INVALID_OPERATION if `index` is past the end.
+        unsafe { gl_call!(DispatchComputeIndirect(offset as GLintptr)) }
+    }
+}
+
 #[derive(Debug)]
 pub struct RawProgram {
     id: GLuint,
@@ -164,53 +515,118 @@ pub struct RawProgram {
 }
 
 impl RawProgram {
-    crate fn new() -> Option<Self> {
-        // UNWRAP: this function never sets an error state
-        let id = unsafe { gl_call!(CreateProgram()).unwrap() };
-        match id {
-            0 => None,
-            id => Some(RawProgram {
+    crate fn new() -> Result<Self, ProgramError> {
+        match unsafe { gl_call!(CreateProgram())? } {
+            0 => Err(ProgramError::Creation),
+            id => Ok(RawProgram {
                 id,
                 _marker: ::std::marker::PhantomData,
             }),
         }
     }
 
-    crate fn bind(&self) {
-        // glUseProgram fails, even though program validation succeeds, and using the program
-        // seems to bind it just fine... Smells like a driver bug to me.
-        unsafe {
-            let _ = gl_call!(UseProgram(self.id));
-        }
+    /// Only works once the program is linked.
+    crate fn bind(&self) -> GlResult<()> {
+        unsafe { gl_call!(UseProgram(self.id)) }
     }
 
-    crate fn attach_shader(&self, shader: CompiledShader) {
-        self.bind();
-        unsafe {
-            gl_call!(AttachShader(self.id, shader.shader.id)).unwrap();
-        }
+    crate fn set_label(&self, label: &str) {
+        debug::label_object(gl::PROGRAM, self.id, label);
+    }
+
+    crate fn attach_shader(&self, shader: CompiledShader) -> GlResult<()> {
+        unsafe { gl_call!(AttachShader(self.id, shader.shader.id)) }
     }
 
     crate fn link(self) -> Result<RawLinkedProgram, ProgramError> {
-        self.bind();
         unsafe {
-            assert!(self.id != 0);
             gl_call!(LinkProgram(self.id))?;
-            check_program_status(self.id, gl::LINK_STATUS)?;
+            if !program_status(self.id, gl::LINK_STATUS)? {
+                return Err(ProgramError::Link(program_info_log(self.id)?));
+            }
             gl_call!(ValidateProgram(self.id))?;
-            check_program_status(self.id, gl::VALIDATE_STATUS)?;
+            if !program_status(self.id, gl::VALIDATE_STATUS)? {
+                return Err(ProgramError::Validation(program_info_log(self.id)?));
+            }
         }
         Ok(RawLinkedProgram(self))
     }
+
+    /// Links the program from a binary that came out of
+    /// `RawLinkedProgram::binary`. Drivers can reject binaries for whatever
+    /// reason they want, in which case the program is handed back so it can be
+    /// linked normally.
+    crate fn link_binary(self, format: GLenum, binary: &[u8]) -> Result<RawLinkedProgram, RawProgram> {
+        unsafe {
+            // Fails if the driver doesn't support `format` anymore
+            if gl_call!(ProgramBinary(self.id, format, binary.as_ptr() as *const _, binary.len() as GLsizei)).is_err() {
+                return Err(self);
+            }
+        }
+        match program_status(self.id, gl::LINK_STATUS) {
+            Ok(true) => Ok(RawLinkedProgram(self)),
+            _ => Err(self),
+        }
+    }
+
+    /// Asks the driver to keep the program's binary around after it's linked,
+    /// so `RawLinkedProgram::binary` has something to give back.
+    crate fn set_binary_retrievable(&self) -> GlResult<()> {
+        unsafe { gl_call!(ProgramParameteri(self.id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as GLint)) }
+    }
+}
+
+// GL waits until the program isn't in use anymore before actually deleting it,
+// so this is fine even if it's still bound.
+impl Drop for RawProgram {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = gl_call!(DeleteProgram(self.id));
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct RawLinkedProgram(RawProgram);
 
+impl RawLinkedProgram {
+    /// The format and contents of the linked program's binary, or `None` if
+    /// the driver doesn't support getting it.
+    crate fn binary(&self) -> Option<(GLenum, Vec<u8>)> {
+        unsafe {
+            let mut length = 0;
+            gl_call!(GetProgramiv(self.0.id, gl::PROGRAM_BINARY_LENGTH, &mut length)).ok()?;
+            if length == 0 {
+                return None;
+            }
+
+            let mut binary = vec![0u8; length as usize];
+            let (mut written, mut format) = (0, 0);
+            gl_call!(GetProgramBinary(self.0.id, length, &mut written, &mut format, binary.as_mut_ptr() as *mut _)).ok()?;
+            binary.truncate(written as usize);
+            Some((format, binary))
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ProgramError {
+    /// `glCreateProgram` didn't give us a program.
+    Creation,
     Uniform(UniformError),
-    Other(String),
+    /// The vertex shader has an input of type `actual`, but the program's input
+    /// type puts an `expected` there.
+    InputMismatch { name: String, location: u32, expected: GlslType, actual: GlslType },
+    /// The vertex shader has an input in a location that the program's input
+    /// type doesn't fill.
+    MissingInput { name: String, location: u32 },
+    /// A shader was given somewhere that only takes `expected` shaders.
+    WrongShaderType { expected: ShaderType, actual: ShaderType },
+    /// The program didn't link. Holds the info log.
+    Link(String),
+    /// The program linked, but can't run in the current state. Holds the info
+    /// log.
+    Validation(String),
     Shader(ShaderError),
     Gl(GlError),
 }
@@ -233,38 +649,183 @@ impl From<UniformError> for ProgramError {
     }
 }
 
-fn check_program_status(id: GLuint, ty: GLenum) -> Result<(), ProgramError> {
-    let mut status = 1;
-    unsafe {
-        gl_call!(GetProgramiv(id, ty, &mut status)).unwrap();
+impl fmt::Display for UniformError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UniformError::NameError(ref name) => write!(f, "the program doesn't have anything called `{}`", name),
+            UniformError::LayoutMismatch { ref block, ref member, expected, actual } => write!(
+                f, "`{}.{}` is at offset {} in the shader, but at {} on the Rust side",
+                block, member, actual, expected,
+            ),
+            UniformError::MissingMember { ref block, ref member } => write!(f, "`{}` doesn't have a member called `{}`", block, member),
+            UniformError::SizeMismatch { ref block, expected, actual } => write!(
+                f, "`{}` is {} bytes in the shader, but {} bytes on the Rust side",
+                block, actual, expected,
+            ),
+            UniformError::StrideMismatch { ref block, expected, actual } => write!(
+                f, "the elements of `{}` are {} bytes apart in the shader, but {} bytes apart on the Rust side",
+                block, actual, expected,
+            ),
+            UniformError::TypeMismatch { ref name, expected, actual } => write!(
+                f, "`{}` is a {} in the shader, but it gets set with a {}",
+                name, actual, expected,
+            ),
+            UniformError::Gl(ref err) => write!(f, "{}", err),
+        }
     }
+}
 
-    if status == 0 {
-        Err(ProgramError::Other(
-            program_info_log(id).unwrap_or(String::new()),
-        ))
-    } else {
-        Ok(())
+impl error::Error for UniformError {
+    fn description(&self) -> &str {
+        "the program's environment doesn't match the shader"
+    }
+
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            UniformError::Gl(ref err) => error::Error::source(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProgramError::Creation => write!(f, "couldn't create a program object"),
+            ProgramError::Uniform(ref err) => write!(f, "{}", err),
+            ProgramError::InputMismatch { ref name, location, expected, actual } => write!(
+                f, "vertex input `{}` at location {} is a {}, but the vertex type puts a {} there",
+                name, location, actual, expected,
+            ),
+            ProgramError::MissingInput { ref name, location } => write!(
+                f, "vertex input `{}` at location {} isn't filled by the vertex type",
+                name, location,
+            ),
+            ProgramError::WrongShaderType { expected, actual } => write!(
+                f, "expected a {:?} shader, but got a {:?} shader",
+                expected, actual,
+            ),
+            ProgramError::Link(ref log) => write!(f, "the program didn't link:\n{}", log),
+            ProgramError::Validation(ref log) => write!(f, "the program didn't validate:\n{}", log),
+            ProgramError::Shader(ref err) => write!(f, "{}", err),
+            ProgramError::Gl(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for ProgramError {
+    fn description(&self) -> &str {
+        "couldn't build a program"
+    }
+
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            ProgramError::Uniform(ref err) => error::Error::source(err),
+            ProgramError::Shader(ref err) => error::Error::source(err),
+            ProgramError::Gl(ref err) => error::Error::source(err),
+            _ => None,
+        }
+    }
+}
+
+// Whether the program's `ty` status, like `GL_LINK_STATUS`, is true.
+fn program_status(id: GLuint, ty: GLenum) -> GlResult<bool> {
+    let mut status = 1;
+    unsafe {
+        gl_call!(GetProgramiv(id, ty, &mut status))?;
     }
+    Ok(status != 0)
 }
 
-fn program_info_log(id: GLuint) -> Option<String> {
+fn program_info_log(id: GLuint) -> GlResult<String> {
     unsafe {
         let mut length = 0;
-        gl_call!(GetProgramiv(id, gl::INFO_LOG_LENGTH, &mut length)).unwrap();
+        gl_call!(GetProgramiv(id, gl::INFO_LOG_LENGTH, &mut length))?;
         if length == 0 {
-            None
-        } else {
-            let mut buffer = Vec::<u8>::with_capacity(length as usize);
-            gl_call!(GetProgramInfoLog(
-                id,
-                length,
-                ::std::ptr::null_mut(),
-                buffer.as_mut_ptr() as *mut i8
-            )).unwrap();
-            buffer.set_len((length - 1) as usize);
+            return Ok(String::new());
+        }
+
+        let mut buffer = vec![0u8; length as usize];
+        gl_call!(GetProgramInfoLog(
+            id,
+            length,
+            ::std::ptr::null_mut(),
+            buffer.as_mut_ptr() as *mut i8
+        ))?;
+        // Drop the nul terminator
+        buffer.pop();
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Vector2, Vector3};
+    use gl_api::shader::reflect::BlockMember;
+
+    std140! {
+        block Camera {
+            zoom: f32,
+            offset: Vector3<f32>,
+        }
+    }
+
+    std430! {
+        struct Particle {
+            position: Vector2<f32>,
+            life: f32,
+        }
+    }
+
+    fn member(name: &str, ty: GlslType, offset: usize, top_level_stride: usize) -> BlockMember {
+        BlockMember { name: name.into(), ty, array_size: 1, offset, top_level_stride }
+    }
+
+    fn block(name: &str, size: usize, members: Vec<BlockMember>) -> Block {
+        Block { name: name.into(), index: 0, binding: 0, size, members }
+    }
+
+    #[test]
+    fn uniform_blocks_match_by_offset() {
+        let camera = block("Camera", 32, vec![
+            member("Camera.zoom", GlslType::Float, 0, 0),
+            member("Camera.offset", GlslType::Vec3, 16, 0),
+        ]);
+        assert!(validate_block::<Camera>(&camera).is_ok());
+
+        let moved = block("Camera", 32, vec![
+            member("zoom", GlslType::Float, 0, 0),
+            member("offset", GlslType::Vec3, 4, 0),
+        ]);
+        match validate_block::<Camera>(&moved) {
+            Err(UniformError::LayoutMismatch { ref member, expected: 16, actual: 4, .. }) if member == "offset" => (),
+            other => panic!("expected a layout mismatch, got {:?}", other),
+        }
+
+        let missing = block("Camera", 32, vec![member("zoom", GlslType::Float, 0, 0)]);
+        match validate_block::<Camera>(&missing) {
+            Err(UniformError::MissingMember { ref member, .. }) if member == "offset" => (),
+            other => panic!("expected a missing member, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn storage_blocks_check_the_stride() {
+        let stride = Particle::std430_stride();
+        let particles = block("Particles", 0, vec![
+            member("items[0].position", GlslType::Vec2, 0, stride),
+            member("items[0].life", GlslType::Float, 8, stride),
+        ]);
+        assert!(validate_storage::<Particle>(&particles).is_ok());
 
-            Some(String::from_utf8(buffer).expect("Program info log was not UTF-8"))
+        let packed = block("Particles", 0, vec![
+            member("items[0].position", GlslType::Vec2, 0, 12),
+            member("items[0].life", GlslType::Float, 8, 12),
+        ]);
+        match validate_storage::<Particle>(&packed) {
+            Err(UniformError::StrideMismatch { actual: 12, .. }) => (),
+            other => panic!("expected a stride mismatch, got {:?}", other),
         }
     }
 }
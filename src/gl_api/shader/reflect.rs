@@ -0,0 +1,276 @@
+use gl;
+use gl::types::*;
+use gl_api::error::GlResult;
+use std::fmt;
+
+macro_rules! glsl_types {
+    ($($variant:ident: $gl:ident $name:expr,)*) => {
+        /// The type of a variable in a shader.
+        #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+        pub enum GlslType {
+            $($variant,)*
+            /// Anything we don't have a name for yet.
+            Other(GLenum),
+        }
+
+        impl GlslType {
+            pub fn from_gl(ty: GLenum) -> Self {
+                match ty {
+                    $(gl::$gl => GlslType::$variant,)*
+                    other => GlslType::Other(other),
+                }
+            }
+
+            /// What the type is called in GLSL.
+            pub fn name(&self) -> Option<&'static str> {
+                match *self {
+                    $(GlslType::$variant => Some($name),)*
+                    GlslType::Other(_) => None,
+                }
+            }
+        }
+    };
+}
+
+glsl_types! {
+    Float: FLOAT "float", Vec2: FLOAT_VEC2 "vec2", Vec3: FLOAT_VEC3 "vec3", Vec4: FLOAT_VEC4 "vec4",
+    Double: DOUBLE "double", DVec2: DOUBLE_VEC2 "dvec2", DVec3: DOUBLE_VEC3 "dvec3", DVec4: DOUBLE_VEC4 "dvec4",
+    Int: INT "int", IVec2: INT_VEC2 "ivec2", IVec3: INT_VEC3 "ivec3", IVec4: INT_VEC4 "ivec4",
+    UInt: UNSIGNED_INT "uint", UVec2: UNSIGNED_INT_VEC2 "uvec2", UVec3: UNSIGNED_INT_VEC3 "uvec3", UVec4: UNSIGNED_INT_VEC4 "uvec4",
+    Bool: BOOL "bool", BVec2: BOOL_VEC2 "bvec2", BVec3: BOOL_VEC3 "bvec3", BVec4: BOOL_VEC4 "bvec4",
+    Mat2: FLOAT_MAT2 "mat2", Mat3: FLOAT_MAT3 "mat3", Mat4: FLOAT_MAT4 "mat4",
+    DMat2: DOUBLE_MAT2 "dmat2", DMat3: DOUBLE_MAT3 "dmat3", DMat4: DOUBLE_MAT4 "dmat4",
+    Sampler2D: SAMPLER_2D "sampler2D", ISampler2D: INT_SAMPLER_2D "isampler2D", USampler2D: UNSIGNED_INT_SAMPLER_2D "usampler2D",
+    Sampler2DArray: SAMPLER_2D_ARRAY "sampler2DArray", Sampler3D: SAMPLER_3D "sampler3D", SamplerCube: SAMPLER_CUBE "samplerCube",
+}
+
+impl GlslType {
+    /// The `n` component vector of `scalar`, or just `scalar` if `n` is 1.
+    pub fn vector(scalar: GlslType, n: usize) -> Self {
+        use self::GlslType::*;
+        match (scalar, n) {
+            (_, 1) => scalar,
+            (Float, 2) => Vec2, (Float, 3) => Vec3, (Float, 4) => Vec4,
+            (Double, 2) => DVec2, (Double, 3) => DVec3, (Double, 4) => DVec4,
+            (Int, 2) => IVec2, (Int, 3) => IVec3, (Int, 4) => IVec4,
+            (UInt, 2) => UVec2, (UInt, 3) => UVec3, (UInt, 4) => UVec4,
+            (Bool, 2) => BVec2, (Bool, 3) => BVec3, (Bool, 4) => BVec4,
+            _ => panic!("there's no {} component vector of {}", n, scalar),
+        }
+    }
+
+    /// Whether something that sets a uniform of type `self` can be used for a
+    /// uniform that was declared as `declared`. Bools don't have their own
+    /// `glUniform*`, so they can be set with any of the other scalars, and the
+    /// sampler types all get set with an int.
+    pub fn can_set(self, declared: GlslType) -> bool {
+        use self::GlslType::*;
+        self == declared || match (self, declared) {
+            (Float, Bool) | (Int, Bool) | (UInt, Bool) => true,
+            (Vec2, BVec2) | (IVec2, BVec2) | (UVec2, BVec2) => true,
+            (Vec3, BVec3) | (IVec3, BVec3) | (UVec3, BVec3) => true,
+            (Vec4, BVec4) | (IVec4, BVec4) | (UVec4, BVec4) => true,
+            (Sampler2D, ISampler2D) | (Sampler2D, USampler2D) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for GlslType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.name(), *self) {
+            (Some(name), _) => write!(f, "{}", name),
+            (None, other) => write!(f, "<unknown type {:?}>", other),
+        }
+    }
+}
+
+/// An attribute or a uniform that isn't in a block.
+#[derive(Clone, Debug)]
+pub struct Variable {
+    pub name: String,
+    pub ty: GlslType,
+    /// 1 for anything that isn't an array.
+    pub array_size: usize,
+    pub location: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockMember {
+    pub name: String,
+    pub ty: GlslType,
+    pub array_size: usize,
+    pub offset: usize,
+    /// How far apart the elements of the outermost array the member is in
+    /// are, or 0 if it isn't in one. Uniform blocks always have 0 here.
+    pub top_level_stride: usize,
+}
+
+/// A uniform block or a storage block.
+#[derive(Clone, Debug)]
+pub struct Block {
+    pub name: String,
+    /// Which block it is, for things like `glUniformBlockBinding`.
+    pub index: GLuint,
+    pub binding: u32,
+    /// The minimum size of the buffer that gets bound to it. For storage
+    /// blocks that end in an unsized array, this doesn't count the array.
+    pub size: usize,
+    pub members: Vec<BlockMember>,
+}
+
+/// Everything a linked program says it uses.
+#[derive(Clone, Debug)]
+pub struct ProgramInterface {
+    pub attributes: Vec<Variable>,
+    /// Only the uniforms that aren't in a uniform block.
+    pub uniforms: Vec<Variable>,
+    pub uniform_blocks: Vec<Block>,
+    pub storage_blocks: Vec<Block>,
+}
+
+impl ProgramInterface {
+    crate fn query(program: GLuint) -> GlResult<Self> {
+        unsafe {
+            let attributes = (0..resource_count(program, gl::PROGRAM_INPUT)?)
+                .map(|index| variable(program, gl::PROGRAM_INPUT, index))
+                .collect::<GlResult<_>>()?;
+
+            // Members of uniform blocks are uniforms too, and they get listed
+            // with their block instead.
+            let mut uniforms = Vec::new();
+            for index in 0..resource_count(program, gl::UNIFORM)? {
+                if resource_properties(program, gl::UNIFORM, index, &[gl::BLOCK_INDEX], 1)?[0] == -1 {
+                    uniforms.push(variable(program, gl::UNIFORM, index)?);
+                }
+            }
+
+            let uniform_blocks = (0..resource_count(program, gl::UNIFORM_BLOCK)?)
+                .map(|index| block(program, gl::UNIFORM_BLOCK, gl::UNIFORM, index))
+                .collect::<GlResult<_>>()?;
+            let storage_blocks = (0..resource_count(program, gl::SHADER_STORAGE_BLOCK)?)
+                .map(|index| block(program, gl::SHADER_STORAGE_BLOCK, gl::BUFFER_VARIABLE, index))
+                .collect::<GlResult<_>>()?;
+
+            Ok(ProgramInterface { attributes, uniforms, uniform_blocks, storage_blocks })
+        }
+    }
+
+    pub fn uniform_block(&self, name: &str) -> Option<&Block> {
+        self.uniform_blocks.iter().find(|block| block.name == name)
+    }
+
+    pub fn storage_block(&self, name: &str) -> Option<&Block> {
+        self.storage_blocks.iter().find(|block| block.name == name)
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&Variable> {
+        self.attributes.iter().find(|attribute| attribute.name == name)
+    }
+
+    /// Arrays can be looked up either as `name` or as `name[0]`.
+    pub fn uniform(&self, name: &str) -> Option<&Variable> {
+        self.uniforms.iter().find(|uniform| {
+            uniform.name == name || (uniform.name.ends_with("[0]") && uniform.name[..uniform.name.len() - 3] == *name)
+        })
+    }
+}
+
+unsafe fn resource_count(program: GLuint, interface: GLenum) -> GlResult<GLuint> {
+    let mut count = 0;
+    gl_call!(GetProgramInterfaceiv(program, interface, gl::ACTIVE_RESOURCES, &mut count))?;
+    Ok(count as GLuint)
+}
+
+unsafe fn variable(program: GLuint, interface: GLenum, index: GLuint) -> GlResult<Variable> {
+    let properties = resource_properties(program, interface, index, &[gl::TYPE, gl::ARRAY_SIZE, gl::LOCATION], 3)?;
+    Ok(Variable {
+        name: resource_name(program, interface, index)?,
+        ty: GlslType::from_gl(properties[0] as GLenum),
+        array_size: properties[1] as usize,
+        location: properties[2],
+    })
+}
+
+unsafe fn block(program: GLuint, interface: GLenum, member_interface: GLenum, index: GLuint) -> GlResult<Block> {
+    let properties = resource_properties(program, interface, index, &[gl::BUFFER_BINDING, gl::BUFFER_DATA_SIZE, gl::NUM_ACTIVE_VARIABLES], 3)?;
+    let count = properties[2] as usize;
+    let mut members = Vec::with_capacity(count);
+    for member in resource_properties(program, interface, index, &[gl::ACTIVE_VARIABLES], count)? {
+        let member = member as GLuint;
+        let properties = resource_properties(program, member_interface, member, &[gl::TYPE, gl::ARRAY_SIZE, gl::OFFSET], 3)?;
+        // Only buffer variables have this, and asking about it for a uniform
+        // is an error.
+        let top_level_stride = if member_interface == gl::BUFFER_VARIABLE {
+            resource_properties(program, member_interface, member, &[gl::TOP_LEVEL_ARRAY_STRIDE], 1)?[0] as usize
+        } else {
+            0
+        };
+        members.push(BlockMember {
+            name: resource_name(program, member_interface, member)?,
+            ty: GlslType::from_gl(properties[0] as GLenum),
+            array_size: properties[1] as usize,
+            offset: properties[2] as usize,
+            top_level_stride,
+        });
+    }
+
+    Ok(Block {
+        name: resource_name(program, interface, index)?,
+        index,
+        binding: properties[0] as u32,
+        size: properties[1] as usize,
+        members,
+    })
+}
+
+// Reads `count` integer properties of a program resource at once.
+unsafe fn resource_properties(program: GLuint, interface: GLenum, index: GLuint, properties: &[GLenum], count: usize) -> GlResult<Vec<GLint>> {
+    let mut values = vec![0; count];
+    gl_call!(GetProgramResourceiv(
+        program, interface, index,
+        properties.len() as i32, properties.as_ptr(),
+        count as i32, ::std::ptr::null_mut(), values.as_mut_ptr()
+    ))?;
+    Ok(values)
+}
+
+unsafe fn resource_name(program: GLuint, interface: GLenum, index: GLuint) -> GlResult<String> {
+    let length = resource_properties(program, interface, index, &[gl::NAME_LENGTH], 1)?[0];
+    let mut buffer = vec![0u8; length as usize];
+    gl_call!(GetProgramResourceName(program, interface, index, length, ::std::ptr::null_mut(), buffer.as_mut_ptr() as *mut GLchar))?;
+    // Drop the nul terminator
+    buffer.pop();
+    Ok(String::from_utf8_lossy(&buffer).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Vector2, Vector3};
+    use gl_api::layout::{SomeType, VertexAttribute};
+    use gl_api::texture::Texture2D;
+    use gl_api::uniform::BoundUniform;
+
+    #[test]
+    fn rust_types_map_to_glsl() {
+        assert_eq!(<Vector2<f32> as BoundUniform>::glsl_type(), GlslType::Vec2);
+        assert_eq!(<Vector3<u32> as BoundUniform>::glsl_type(), GlslType::UVec3);
+        assert_eq!(Texture2D::glsl_type(), GlslType::Sampler2D);
+
+        // Doubles get converted to floats by `glVertexAttribPointer`, so they
+        // can only feed float inputs.
+        assert_eq!(<Vector2<f32> as VertexAttribute>::attrib_types(), vec![GlslType::Vec2]);
+        assert_eq!(SomeType::attrib_types(), vec![GlslType::Int, GlslType::Vec3]);
+    }
+
+    #[test]
+    fn setting_bools_and_samplers() {
+        assert!(GlslType::Int.can_set(GlslType::Bool));
+        assert!(GlslType::Vec3.can_set(GlslType::BVec3));
+        assert!(!GlslType::Vec3.can_set(GlslType::BVec2));
+        assert!(GlslType::Sampler2D.can_set(GlslType::USampler2D));
+        assert!(!GlslType::Float.can_set(GlslType::Int));
+        assert_eq!(GlslType::vector(GlslType::Int, 4).to_string(), "ivec4");
+    }
+}
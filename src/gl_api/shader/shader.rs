@@ -1,27 +1,28 @@
-use std::path::Path;
-use std::fs::File;
-use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::error;
+use std::fmt;
+use std::io;
+use gl_api::error::{GlError, GlResult};
+use gl_api::shader::diagnostic::CompileError;
 use std::ptr;
+use gl_api::shader::preprocess::{Defines, PreprocessError, Preprocessor, INCLUDE_ROOT};
 use gl::types::*;
 use gl;
 
-fn shader_info_log(shader: &Shader) -> Option<String> {
+fn shader_info_log(shader: &Shader) -> GlResult<String> {
     let id = shader.id;
     unsafe {
         let mut length = 0;
-        gl_call!(GetShaderiv(id, gl::INFO_LOG_LENGTH, &mut length)).unwrap();
-
+        gl_call!(GetShaderiv(id, gl::INFO_LOG_LENGTH, &mut length))?;
         if length == 0 {
-            println!("Log was empty :(");
-            None
-        } else {
-            let mut buffer = Vec::<u8>::with_capacity(length as usize);
-            // TODO: unwrap
-            gl_call!(GetShaderInfoLog(id, length, ptr::null_mut(), buffer.as_mut_ptr() as *mut i8)).unwrap();
-            buffer.set_len((length - 1) as usize);
-
-            Some(String::from_utf8(buffer).expect("Shader info log was not UTF-8"))
+            return Ok(String::new());
         }
+
+        let mut buffer = vec![0u8; length as usize];
+        gl_call!(GetShaderInfoLog(id, length, ptr::null_mut(), buffer.as_mut_ptr() as *mut i8))?;
+        // Drop the nul terminator
+        buffer.pop();
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
     }
 }
 
@@ -40,66 +41,123 @@ pub enum ShaderType {
 #[derive(Debug)]
 pub enum ShaderError {
     Creation,
-    Shader(String),
+    Gl(GlError),
+    Compile(CompileError),
     Io(io::Error),
+    Preprocess(PreprocessError),
 }
 
 pub type ShaderResult<T> = Result<T, ShaderError>;
 
+impl From<GlError> for ShaderError {
+    fn from(err: GlError) -> Self { ShaderError::Gl(err) }
+}
+
 impl From<io::Error> for ShaderError {
     fn from(err: io::Error) -> Self { ShaderError::Io(err) }
 }
 
+impl From<PreprocessError> for ShaderError {
+    fn from(err: PreprocessError) -> Self { ShaderError::Preprocess(err) }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ShaderError::Creation => write!(f, "couldn't create a shader object"),
+            ShaderError::Gl(ref err) => write!(f, "{}", err),
+            ShaderError::Compile(ref err) => write!(f, "{}", err),
+            ShaderError::Io(ref err) => write!(f, "couldn't read a shader: {}", err),
+            ShaderError::Preprocess(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for ShaderError {
+    fn description(&self) -> &str {
+        "couldn't build a shader"
+    }
+
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            ShaderError::Creation => None,
+            ShaderError::Gl(ref err) => error::Error::source(err),
+            ShaderError::Compile(ref err) => error::Error::source(err),
+            ShaderError::Io(ref err) => Some(err),
+            ShaderError::Preprocess(ref err) => error::Error::source(err),
+        }
+    }
+}
+
 pub struct Shader {
     pub(in super) shader_type: ShaderType,
     pub(in super) id: GLuint,
+    // Where the source came from, for error messages.
+    files: Vec<PathBuf>,
+    // Everything that was passed to `glShaderSource`, so programs can tell
+    // whether they've been built from the same thing before.
+    pub(in super) source: Vec<u8>,
 }
 
 impl Shader {
     pub fn new(shader_type: ShaderType) -> ShaderResult<Self> {
-        let id = unsafe { gl_call!(CreateShader(shader_type as u32)).unwrap() };
+        let id = unsafe { gl_call!(CreateShader(shader_type as u32))? };
         if id == 0 { return Err(ShaderError::Creation) }
-        Ok(Shader { shader_type, id })
+        Ok(Shader { shader_type, id, files: Vec::new(), source: Vec::new() })
+    }
+
+    /// Loads the shader at `path`, with any `#include`s pulled in from `res/`.
+    pub fn source_from_file<P: AsRef<Path>>(&mut self, path: P) -> ShaderResult<()> {
+        self.source_from_file_with(path, &Defines::new())
     }
 
-    pub fn source_from_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
-        let mut buf = String::new();
-        let mut file = File::open(path)?;
-        file.read_to_string(&mut buf)?;
-        self.shader_source(buf);
+    /// Same as `source_from_file`, but with some extra `#define`s at the top.
+    pub fn source_from_file_with<P: AsRef<Path>>(&mut self, path: P, defines: &Defines) -> ShaderResult<()> {
+        let processed = Preprocessor::new(INCLUDE_ROOT, defines).process(path)?;
+        self.shader_source(processed.source)?;
+        self.files = processed.files;
         Ok(())
     }
 
-    pub fn shader_source<S: AsRef<[u8]>>(&self, source: S) {
-        let source = source.as_ref();
+    /// The files the source was loaded from, if it came from files.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    pub fn shader_source<S: AsRef<[u8]>>(&mut self, source: S) -> ShaderResult<()> {
+        self.shader_source_many(&[source])
+    }
+
+    /// Sets the source to all of `sources` stuck together, in order.
+    pub fn shader_source_many<S: AsRef<[u8]>>(&mut self, sources: &[S]) -> ShaderResult<()> {
+        self.files.clear();
+        self.source = sources.iter().flat_map(|source| source.as_ref().iter().cloned()).collect();
+        let pointers = sources.iter().map(|source| source.as_ref().as_ptr() as *const GLchar).collect::<Vec<_>>();
+        let lengths = sources.iter().map(|source| source.as_ref().len() as i32).collect::<Vec<_>>();
         unsafe {
-            gl_call!(ShaderSource(self.id, 1,
-                &(source.as_ptr() as *const GLchar),
-                &(source.len() as i32))).unwrap();
+            gl_call!(ShaderSource(self.id, sources.len() as i32, pointers.as_ptr(), lengths.as_ptr()))?;
         }
+        Ok(())
     }
 
     pub fn compile(self) -> ShaderResult<CompiledShader> {
         unsafe {
-            // UNWRAP: `self.id` is a valid shader handle, and no other GL errors are emitted here;
-            // the compile status is part of the Shader object's state
             let mut status = 1;
-            gl_call!(CompileShader(self.id)).unwrap();
-            gl_call!(GetShaderiv(self.id, gl::COMPILE_STATUS, &mut status)).unwrap();
+            gl_call!(CompileShader(self.id))?;
+            gl_call!(GetShaderiv(self.id, gl::COMPILE_STATUS, &mut status))?;
             if status == 0 {
-                let log = shader_info_log(&self).unwrap_or_default();
-                Err(ShaderError::Shader(log))
+                let log = shader_info_log(&self)?;
+                Err(ShaderError::Compile(CompileError::new(self.shader_type, log, &self.files)))
             } else {
                 Ok(CompiledShader { shader: self })
             }
         }
     }
-    // TODO: pub fn shader_source_many(&self, )
 }
 
 impl Drop for Shader {
     fn drop(&mut self) {
-        unsafe { gl_call!(DeleteShader(self.id)).unwrap(); }
+        unsafe { let _ = gl_call!(DeleteShader(self.id)); }
     }
 }
 
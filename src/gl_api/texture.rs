@@ -1,11 +1,16 @@
 use image::RgbaImage;
 use image::ImageError;
 use std::cell::Cell;
+use std::error;
+use std::fmt;
 use std::ops::Deref;
 use std::path::Path;
 use image::{self, ImageBuffer, DynamicImage, Pixel};
 use gl::types::*;
+use gl_api::debug;
+use gl_api::error::{GlError, GlResult};
 use gl;
+use gl_api::shader::reflect::GlslType;
 use gl_api::uniform::{BoundUniform, Uniform};
 
 pub type TextureResult<T> = Result<T, TextureError>;
@@ -13,12 +18,44 @@ pub type TextureResult<T> = Result<T, TextureError>;
 pub enum TextureError {
     Image(ImageError),
     TextureTooLarge(u32, u32),
+    /// The texture unit, and how many units the driver has.
+    NoSuchTextureUnit(usize, usize),
+    Gl(GlError),
 }
 
 impl From<ImageError> for TextureError {
     fn from(err: ImageError) -> Self { TextureError::Image(err) }
 }
 
+impl From<GlError> for TextureError {
+    fn from(err: GlError) -> Self { TextureError::Gl(err) }
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TextureError::Image(ref err) => write!(f, "couldn't load the image: {}", err),
+            TextureError::TextureTooLarge(width, height) => write!(f, "{}x{} is too big for a texture", width, height),
+            TextureError::NoSuchTextureUnit(unit, count) => write!(f, "there's no texture unit {}, the driver only has {}", unit, count),
+            TextureError::Gl(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for TextureError {
+    fn description(&self) -> &str {
+        "couldn't make a texture"
+    }
+
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            TextureError::Image(ref err) => Some(err),
+            TextureError::TextureTooLarge(..) | TextureError::NoSuchTextureUnit(..) => None,
+            TextureError::Gl(ref err) => error::Error::source(err),
+        }
+    }
+}
+
 #[repr(u32)]
 pub enum MinimizationFilter {
     Nearest = gl::NEAREST,
@@ -44,135 +81,193 @@ pub enum WrapMode {
     MirrorClampToEdge = gl::MIRROR_CLAMP_TO_EDGE,
 }
 
+/// Formats for textures that get drawn into rather than loaded from an image.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[repr(u32)]
+pub enum TextureFormat {
+    Rgba8 = gl::RGBA8,
+    Rgba16F = gl::RGBA16F,
+    Depth24 = gl::DEPTH_COMPONENT24,
+    Depth32F = gl::DEPTH_COMPONENT32F,
+    Depth24Stencil8 = gl::DEPTH24_STENCIL8,
+}
+
+impl TextureFormat {
+    // The format and type `glTexImage2D` wants alongside the internal format.
+    // Nothing is actually uploaded, but they still have to be compatible.
+    fn pixel_transfer(&self) -> (GLenum, GLenum) {
+        match *self {
+            TextureFormat::Rgba8 => (gl::RGBA, gl::UNSIGNED_BYTE),
+            TextureFormat::Rgba16F => (gl::RGBA, gl::FLOAT),
+            TextureFormat::Depth24 => (gl::DEPTH_COMPONENT, gl::UNSIGNED_INT),
+            TextureFormat::Depth32F => (gl::DEPTH_COMPONENT, gl::FLOAT),
+            TextureFormat::Depth24Stencil8 => (gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8),
+        }
+    }
+}
+
 pub enum TextureAxis {
     S, T, R
 }
 
 pub trait Texture {
-    fn texture_wrap_behavior(&self, axis: TextureAxis, mode: WrapMode);
-    fn min_filter(&self, mode: MinimizationFilter);
-    fn mag_filter(&self, mode: MagnificationFilter);
+    fn texture_wrap_behavior(&self, axis: TextureAxis, mode: WrapMode) -> GlResult<()>;
+    fn min_filter(&self, mode: MinimizationFilter) -> GlResult<()>;
+    fn mag_filter(&self, mode: MagnificationFilter) -> GlResult<()>;
 }
 
+// Textures can't be bigger than the driver's `GL_MAX_TEXTURE_SIZE` on either
+// side.
+fn check_size(width: u32, height: u32) -> TextureResult<()> {
+    let mut max = 0;
+    unsafe {
+        gl_call!(GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max))?;
+    }
+    if width > max as u32 || height > max as u32 {
+        return Err(TextureError::TextureTooLarge(width, height));
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
 pub struct Texture2D {
-    id: GLuint,
+    crate id: GLuint,
     texture_slot: Cell<GLenum>,
 }
 
 impl Texture2D {
-    pub fn new() -> Self {
+    pub fn new() -> GlResult<Self> {
         let mut id = 0;
-        unsafe { gl_call!(GenTextures(1, &mut id)).unwrap(); }
-        Texture2D { id, texture_slot: Cell::new(0) }
+        unsafe { gl_call!(GenTextures(1, &mut id))?; }
+        Ok(Texture2D { id, texture_slot: Cell::new(0) })
     }
 
     pub fn source(&self, image: DynamicImage) -> TextureResult<()> {
-        self.bind();
+        self.bind()?;
         #[allow(dead_code)]
         fn tex_image<P, C>(format: GLenum, buffer: &ImageBuffer<P, C>) -> TextureResult<()>
         where P: Pixel + 'static,
                 P::Subpixel: 'static,
                 C: Deref<Target=[P::Subpixel]> {
             let (width, height) = buffer.dimensions();
-
-            if width > gl::MAX_TEXTURE_SIZE || height > gl::MAX_TEXTURE_SIZE {
-                return Err(TextureError::TextureTooLarge(width, height));
-            }
+            check_size(width, height)?;
             unsafe {
                 Ok(gl_call!(TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA8 as GLint,
                                 width as i32, height as i32, 0, format,
-                                gl::UNSIGNED_BYTE, buffer.as_ptr() as *const _)).unwrap())
+                                gl::UNSIGNED_BYTE, buffer.as_ptr() as *const _))?)
             }
         };
 
         match image {
             DynamicImage::ImageRgb8(image) => tex_image(gl::RGB, &image)?,
             DynamicImage::ImageRgba8(image) => tex_image(gl::RGBA, &image)?,
-            _ => unimplemented!("luma images are not supported."),
+            // Anything else, like grayscale, just gets expanded out to RGBA
+            other => tex_image(gl::RGBA, &other.to_rgba())?,
         }
 
         Ok(())
     }
 
+    /// Allocates storage for the texture without filling it in, for textures
+    /// that are going to be rendered into.
+    pub fn allocate(&self, width: u32, height: u32, format: TextureFormat) -> TextureResult<()> {
+        self.bind()?;
+        check_size(width, height)?;
+
+        let (pixel_format, pixel_type) = format.pixel_transfer();
+        unsafe {
+            gl_call!(TexImage2D(gl::TEXTURE_2D, 0, format as GLint,
+                                width as i32, height as i32, 0, pixel_format,
+                                pixel_type, ::std::ptr::null()))?;
+        }
+        Ok(())
+    }
+
     pub fn source_from_image<P: AsRef<Path>>(&self, path: P) -> TextureResult<()> {
-        self.bind();
         let image = image::open(path)?;
         self.source(image)
     }
 
-    fn generate_mipmap(&self) {
-        self.bind();
-        unsafe {
-            gl_call!(GenerateMipmap(gl::TEXTURE_2D)).unwrap();
-        }
+    fn generate_mipmap(&self) -> GlResult<()> {
+        self.bind()?;
+        unsafe { gl_call!(GenerateMipmap(gl::TEXTURE_2D)) }
     }
 
-    pub fn bind(&self) {
-        unsafe {
-            gl_call!(BindTexture(gl::TEXTURE_2D, self.id)).unwrap();
-        }
+    pub fn bind(&self) -> GlResult<()> {
+        unsafe { gl_call!(BindTexture(gl::TEXTURE_2D, self.id)) }
+    }
+
+    /// Names the texture in debug messages.
+    pub fn set_label(&self, label: &str) -> GlResult<()> {
+        // Textures don't exist until they're first bound
+        self.bind()?;
+        debug::label_object(gl::TEXTURE, self.id, label);
+        Ok(())
     }
 
-    pub fn set_texture_bank(&self, slot: usize) {
-        assert!(slot <= gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS as usize);
+    pub fn set_texture_bank(&self, slot: usize) -> TextureResult<()> {
+        let mut units = 0;
         unsafe {
-            self.bind();
-            self.texture_slot.set(slot as GLenum);
-            gl_call!(ActiveTexture(gl::TEXTURE0 + slot as GLenum)).unwrap();
+            gl_call!(GetIntegerv(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS, &mut units))?;
+        }
+        if slot >= units as usize {
+            return Err(TextureError::NoSuchTextureUnit(slot, units as usize));
         }
+
+        self.bind()?;
+        self.texture_slot.set(slot as GLenum);
+        unsafe { Ok(gl_call!(ActiveTexture(gl::TEXTURE0 + slot as GLenum))?) }
     }
 }
 
 impl Texture for Texture2D {
-    fn texture_wrap_behavior(&self, axis: TextureAxis, mode: WrapMode) {
-        self.bind();
+    fn texture_wrap_behavior(&self, axis: TextureAxis, mode: WrapMode) -> GlResult<()> {
+        self.bind()?;
         let axis = match axis {
             TextureAxis::S => gl::TEXTURE_WRAP_S,
             TextureAxis::T => gl::TEXTURE_WRAP_T,
             TextureAxis::R => gl::TEXTURE_WRAP_R,
         };
 
-        unsafe {
-            gl_call!(TexParameteri(gl::TEXTURE_2D, axis, mode as i32)).unwrap();
-        }
+        unsafe { gl_call!(TexParameteri(gl::TEXTURE_2D, axis, mode as i32)) }
     }
 
-    fn min_filter(&self, mode: MinimizationFilter) {
-        self.bind();
+    fn min_filter(&self, mode: MinimizationFilter) -> GlResult<()> {
+        self.bind()?;
 
         // Generate mipmaps if the minimization filter uses mipmaps
         match mode {
             MinimizationFilter::LinearMipmapLinear |
             MinimizationFilter::LinearMipmapNearest |
             MinimizationFilter::NearestMipmapLinear |
-            MinimizationFilter::NearestMipmapNearest => self.generate_mipmap(),
+            MinimizationFilter::NearestMipmapNearest => self.generate_mipmap()?,
             _ => (),
         }
 
-        unsafe {
-            gl_call!(TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, mode as i32)).unwrap();
-        }
+        unsafe { gl_call!(TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, mode as i32)) }
     }
 
-    fn mag_filter(&self, mode: MagnificationFilter) {
-        self.bind();
-        unsafe {
-            gl_call!(TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mode as i32)).unwrap();
-        }
+    fn mag_filter(&self, mode: MagnificationFilter) -> GlResult<()> {
+        self.bind()?;
+        unsafe { gl_call!(TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mode as i32)) }
     }
 }
 
 impl Drop for Texture2D {
     fn drop(&mut self) {
         unsafe {
-            gl_call!(DeleteTextures(1, &self.id)).unwrap();
+            let _ = gl_call!(DeleteTextures(1, &self.id));
         }
     }
 }
 
 impl BoundUniform for Texture2D {
     #[inline(always)]
-    fn set(&self, uniform: &Uniform<Self>) {
-        unsafe { gl_call!(Uniform1i(uniform.location, self.texture_slot.get() as i32)).unwrap(); }
+    fn set(&self, uniform: &Uniform<Self>) -> GlResult<()> {
+        unsafe { gl_call!(Uniform1i(uniform.location, self.texture_slot.get() as i32)) }
+    }
+
+    fn glsl_type() -> GlslType {
+        GlslType::Sampler2D
     }
 }
@@ -1,5 +1,7 @@
 use super::error::GlResult;
+use gl;
 use gl::types::*;
+use gl_api::debug;
 use gl_api::buffer::VertexBuffer;
 use gl_api::layout::VertexAttribute;
 
@@ -11,31 +13,35 @@ pub struct VertexArray {
 }
 
 impl VertexArray {
-    pub fn new() -> Self {
+    pub fn new() -> GlResult<Self> {
         let mut id = 0;
-        // UNWRAP: Can only fail if count is negative
         unsafe {
-            gl_call!(GenVertexArrays(1, &mut id)).unwrap();
+            gl_call!(GenVertexArrays(1, &mut id))?;
         }
-        VertexArray {
+        Ok(VertexArray {
             id,
             index: 0,
             _marker: ::std::marker::PhantomData,
-        }
+        })
     }
 
-    pub fn bind(&self) {
-        // UNWRAP: our ID should always be valid
-        unsafe {
-            gl_call!(BindVertexArray(self.id)).unwrap();
-        }
+    pub fn bind(&self) -> GlResult<()> {
+        unsafe { gl_call!(BindVertexArray(self.id)) }
+    }
+
+    /// Names the vertex array in debug messages.
+    pub fn set_label(&self, label: &str) -> GlResult<()> {
+        // Vertex arrays don't exist until they're first bound
+        self.bind()?;
+        debug::label_object(gl::VERTEX_ARRAY, self.id, label);
+        Ok(())
     }
 
     // NOTE: need explicit lifetimes here because the buffer needs to outlive
     // `self`
     pub fn add_buffer<'s, 'b: 's, T: VertexAttribute>(&'s mut self, buffer: &'b VertexBuffer<T>) -> GlResult<()> {
-        self.bind();
-        buffer.bind();
+        self.bind()?;
+        buffer.bind()?;
 
         self.index += T::define_attribs(self.index as u32, 0)? as usize;
 
@@ -60,6 +60,10 @@ impl<T, O> Grid<T, O> {
         (self.width, self.height)
     }
 
+    pub fn in_bounds(&self, pos: Vector2<usize>) -> bool {
+        pos.x < self.width && pos.y < self.height
+    }
+
     pub fn iter(&self) -> impl Iterator<Item=&T> {
         self.data.iter()
     }
@@ -69,32 +73,35 @@ use cgmath::Vector2;
 use std::marker::PhantomData;
 use std::ops::{Index, IndexMut};
 
-impl<T> Index<Vector2<usize>> for Grid<T, AdjacentX> {
-    type Output = T;
-    fn index(&self, Vector2 { x, y }: Vector2<usize>) -> &Self::Output {
-        assert!(x < self.width && y < self.height);
-        &self.data[self.width * y + x]
+/// Describes how a grid orientation lays its cells out in memory.
+pub trait Orientation {
+    /// Gets the index into the grid's backing storage for the cell at `(x, y)`.
+    fn linear_index(x: usize, y: usize, width: usize, height: usize) -> usize;
+}
+
+impl Orientation for AdjacentX {
+    fn linear_index(x: usize, y: usize, width: usize, _height: usize) -> usize {
+        width * y + x
     }
 }
 
-impl<T> Index<Vector2<usize>> for Grid<T, AdjacentY> {
-    type Output = T;
-    fn index(&self, Vector2 { x, y }: Vector2<usize>) -> &Self::Output {
-        assert!(x < self.width && y < self.height);
-        &self.data[self.height * x + y]
+impl Orientation for AdjacentY {
+    fn linear_index(x: usize, y: usize, _width: usize, height: usize) -> usize {
+        height * x + y
     }
 }
 
-impl<T> IndexMut<Vector2<usize>> for Grid<T, AdjacentX> {
-    fn index_mut(&mut self, Vector2 { x, y }: Vector2<usize>) -> &mut Self::Output {
+impl<T, O: Orientation> Index<Vector2<usize>> for Grid<T, O> {
+    type Output = T;
+    fn index(&self, Vector2 { x, y }: Vector2<usize>) -> &Self::Output {
         assert!(x < self.width && y < self.height);
-        &mut self.data[self.width * y + x]
+        &self.data[O::linear_index(x, y, self.width, self.height)]
     }
 }
 
-impl<T> IndexMut<Vector2<usize>> for Grid<T, AdjacentY> {
+impl<T, O: Orientation> IndexMut<Vector2<usize>> for Grid<T, O> {
     fn index_mut(&mut self, Vector2 { x, y }: Vector2<usize>) -> &mut Self::Output {
         assert!(x < self.width && y < self.height);
-        &mut self.data[self.height * x + y]
+        &mut self.data[O::linear_index(x, y, self.width, self.height)]
     }
 }
@@ -0,0 +1,277 @@
+use cgmath::Vector2;
+use glutin::{ElementState, KeyboardInput, VirtualKeyCode};
+use specs::prelude::*;
+use specs::shred::PanicHandler;
+use std::collections::VecDeque;
+use terrain::TerrainKind;
+use turn::{Energy, TurnScheduler, ACTION_COST};
+use {TileGrid, TilePos};
+
+/// One of the eight directions something on the grid can move in. Remember
+/// that y points up!
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl Direction {
+    pub fn delta(&self) -> (isize, isize) {
+        match *self {
+            Direction::Up => (0, 1),
+            Direction::Down => (0, -1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+            Direction::UpLeft => (-1, 1),
+            Direction::UpRight => (1, 1),
+            Direction::DownLeft => (-1, -1),
+            Direction::DownRight => (1, -1),
+        }
+    }
+}
+
+/// Something the player asked to do.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Action {
+    Move(Direction),
+    Wait,
+    PickUp,
+}
+
+impl Action {
+    /// Maps a key press to an action. Movement is bound to the arrow keys, the
+    /// numpad, and the vi keys (`hjkl` plus `yubn` for diagonals).
+    pub fn from_key(input: &KeyboardInput) -> Option<Self> {
+        use glutin::VirtualKeyCode::*;
+
+        if input.state != ElementState::Pressed {
+            return None;
+        }
+
+        let key: VirtualKeyCode = input.virtual_keycode?;
+        Some(match key {
+            Up | Numpad8 | K => Action::Move(Direction::Up),
+            Down | Numpad2 | J => Action::Move(Direction::Down),
+            Left | Numpad4 | H => Action::Move(Direction::Left),
+            Right | Numpad6 | L => Action::Move(Direction::Right),
+            Numpad7 | Y => Action::Move(Direction::UpLeft),
+            Numpad9 | U => Action::Move(Direction::UpRight),
+            Numpad1 | B => Action::Move(Direction::DownLeft),
+            Numpad3 | N => Action::Move(Direction::DownRight),
+            Numpad5 | Period => Action::Wait,
+            G | Comma => Action::PickUp,
+            _ => return None,
+        })
+    }
+
+    /// Maps a typed character to an action, for when there's no window to
+    /// get key presses from. Only the vi keys are available here.
+    pub fn from_char(ch: char) -> Option<Self> {
+        Some(match ch {
+            'k' => Action::Move(Direction::Up),
+            'j' => Action::Move(Direction::Down),
+            'h' => Action::Move(Direction::Left),
+            'l' => Action::Move(Direction::Right),
+            'y' => Action::Move(Direction::UpLeft),
+            'u' => Action::Move(Direction::UpRight),
+            'b' => Action::Move(Direction::DownLeft),
+            'n' => Action::Move(Direction::DownRight),
+            '.' => Action::Wait,
+            'g' | ',' => Action::PickUp,
+            _ => return None,
+        })
+    }
+}
+
+/// Actions the player has entered but that haven't been carried out yet.
+#[derive(Debug, Default)]
+pub struct ActionQueue {
+    actions: VecDeque<Action>,
+}
+
+impl ActionQueue {
+    pub fn push(&mut self, action: Action) {
+        self.actions.push_back(action);
+    }
+
+    pub fn pop(&mut self) -> Option<Action> {
+        self.actions.pop_front()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+/// Marks the entity controlled by the keyboard.
+#[derive(Default)]
+pub struct Player;
+
+impl Component for Player {
+    type Storage = NullStorage<Self>;
+}
+
+/// Carries out the player's next queued action once it's their turn.
+pub struct PlayerMovement;
+
+impl<'a> System<'a> for PlayerMovement {
+    type SystemData = (
+        Write<'a, ActionQueue>,
+        Read<'a, TurnScheduler>,
+        Read<'a, TileGrid, PanicHandler>,
+        Entities<'a>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, TerrainKind>,
+        WriteStorage<'a, Energy>,
+        WriteStorage<'a, TilePos>,
+    );
+
+    fn run(&mut self, (mut queue, scheduler, grid, entities, players, kinds, mut energies, mut positions): Self::SystemData) {
+        let player = match (&*entities, &players).join().next() {
+            Some((player, _)) => player,
+            None => return,
+        };
+        if !scheduler.is_ready(player) {
+            return;
+        }
+
+        let action = match queue.pop() {
+            Some(action) => action,
+            None => return,
+        };
+
+        let acted = match action {
+            Action::Move(direction) => {
+                let (dx, dy) = direction.delta();
+                let (width, height) = grid.dimensions();
+                match positions.get_mut(player) {
+                    Some(pos) => {
+                        let (x, y) = (pos.x as isize + dx, pos.y as isize + dy);
+                        let in_bounds = x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height;
+                        let target = Vector2::new(x as usize, y as usize);
+                        // Walking into a wall doesn't use up the turn.
+                        if in_bounds && kinds.get(grid[target]).map_or(false, |kind| !kind.is_solid()) {
+                            **pos = target;
+                            true
+                        } else {
+                            false
+                        }
+                    }
+                    None => false,
+                }
+            }
+            // There's nothing to pick up yet, so this just passes the turn.
+            Action::Wait | Action::PickUp => true,
+        };
+
+        if acted {
+            if let Some(energy) = energies.get_mut(player) {
+                energy.0 -= ACTION_COST;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glutin::ModifiersState;
+    use grid::GridX;
+    use turn::{Speed, TurnSystem};
+
+    fn key(state: ElementState, key: VirtualKeyCode) -> KeyboardInput {
+        KeyboardInput { scancode: 0, state, virtual_keycode: Some(key), modifiers: ModifiersState::default() }
+    }
+
+    #[test]
+    fn keys_and_chars_map_to_actions() {
+        use glutin::VirtualKeyCode::*;
+
+        for &(code, action) in &[
+            (Up, Action::Move(Direction::Up)),
+            (Numpad2, Action::Move(Direction::Down)),
+            (H, Action::Move(Direction::Left)),
+            (Numpad9, Action::Move(Direction::UpRight)),
+            (N, Action::Move(Direction::DownRight)),
+            (Period, Action::Wait),
+            (G, Action::PickUp),
+        ] {
+            assert_eq!(Action::from_key(&key(ElementState::Pressed, code)), Some(action));
+        }
+        assert_eq!(Action::from_key(&key(ElementState::Released, Up)), None);
+        assert_eq!(Action::from_key(&key(ElementState::Pressed, Q)), None);
+
+        assert_eq!(Action::from_char('y'), Some(Action::Move(Direction::UpLeft)));
+        assert_eq!(Action::from_char('b'), Some(Action::Move(Direction::DownLeft)));
+        assert_eq!(Action::from_char('.'), Some(Action::Wait));
+        assert_eq!(Action::from_char(','), Some(Action::PickUp));
+        assert_eq!(Action::from_char('q'), None);
+    }
+
+    // A player standing at `start` on a map made out of `rows`, where `#` is a
+    // wall. The first row is the top of the map.
+    fn world(rows: &[&str], start: Vector2<usize>) -> (World, Entity) {
+        let mut world = World::new();
+        world.register::<Player>();
+        world.register::<Speed>();
+        world.register::<Energy>();
+        world.register::<TerrainKind>();
+        world.register::<TilePos>();
+
+        let (width, height) = (rows[0].len(), rows.len());
+        let cells = rows.iter().rev()
+            .flat_map(|row| row.chars())
+            .map(|ch| world.create_entity()
+                .with(if ch == '#' { TerrainKind::Wall } else { TerrainKind::Floor })
+                .build())
+            .collect::<Vec<_>>();
+        world.add_resource(TileGrid(GridX::from_iter(cells, width, height)));
+        world.add_resource(TurnScheduler::default());
+        world.add_resource(ActionQueue::default());
+
+        let player = world.create_entity()
+            .with(Player)
+            .with(TilePos(start))
+            .with(Energy(ACTION_COST))
+            .with(Speed::default())
+            .build();
+        (world, player)
+    }
+
+    // Plays out `action`, and says where the player ended up and how much
+    // energy they have left.
+    fn act(world: &mut World, player: Entity, action: Action) -> (Vector2<usize>, i32) {
+        world.write_resource::<ActionQueue>().push(action);
+        TurnSystem.run_now(&world.res);
+        PlayerMovement.run_now(&world.res);
+        assert!(world.read_resource::<ActionQueue>().is_empty());
+
+        let pos = **world.read_storage::<TilePos>().get(player).unwrap();
+        (pos, world.read_storage::<Energy>().get(player).unwrap().0)
+    }
+
+    #[test]
+    fn blocked_moves_are_free() {
+        let (mut world, player) = world(&["#.."], Vector2::new(1, 0));
+        assert_eq!(act(&mut world, player, Action::Move(Direction::Left)), (Vector2::new(1, 0), ACTION_COST));
+        assert_eq!(act(&mut world, player, Action::Move(Direction::Down)), (Vector2::new(1, 0), ACTION_COST));
+        assert_eq!(act(&mut world, player, Action::Move(Direction::UpRight)), (Vector2::new(1, 0), ACTION_COST));
+    }
+
+    #[test]
+    fn acting_uses_energy() {
+        let (mut world, player) = world(&["#..", "..."], Vector2::new(1, 1));
+        assert_eq!(act(&mut world, player, Action::Move(Direction::DownRight)), (Vector2::new(2, 0), 0));
+
+        for &action in &[Action::Wait, Action::PickUp] {
+            world.write_storage::<Energy>().get_mut(player).unwrap().0 = ACTION_COST;
+            assert_eq!(act(&mut world, player, action), (Vector2::new(2, 0), 0));
+        }
+    }
+}
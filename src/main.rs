@@ -9,17 +9,40 @@ extern crate rand;
 
 #[macro_use]
 mod gl_api;
+mod assets;
+mod fog;
+mod fov;
 mod grid;
+mod input;
+mod mapgen;
+mod pathfinding;
+mod post;
+mod render;
+mod software;
+mod terminal;
+mod terrain;
 mod tiles;
-
+mod turn;
+
+use assets::{AssetWatcher, ChangedAssets, HotProgram};
+use fog::{FogOfWar, FogOfWarSystem, Observer, Revealed};
+use fov::{Viewshed, ViewshedSystem};
+use input::{Action, ActionQueue, Player, PlayerMovement};
+use post::{PostChain, PostPass};
+use render::{ActorSprite, Cell, Frame, RenderSystem, Renderer};
+use software::SoftwareRenderer;
+use terminal::TerminalRenderer;
+use terrain::{Autotiler, TerrainKind};
 use tiles::Tile;
+use turn::{Energy, Speed, TurnScheduler, TurnSystem, ACTION_COST};
 use image::GenericImage;
 use gl_api::texture::Texture;
 use gl_api::texture::*;
 use rand::Rng;
 use cgmath::Vector3;
 use cgmath::{Vector2, Vector4};
-use gl_api::buffer::{ShaderStorageBuffer, VertexBuffer};
+use gl_api::block::StorageBuffer;
+use gl_api::buffer::VertexBuffer;
 use gl_api::uniform::Uniform;
 use gl_api::vertex_array::VertexArray;
 use glutin::GlContext;
@@ -27,9 +50,15 @@ use glutin::{Api, GlRequest};
 use specs::shred::PanicHandler;
 use std::marker::PhantomData;
 
-use gl_api::buffer::UsageType;
+use gl_api::buffer::{BufferMapError, UsageType};
+use gl_api::debug::{self, DebugOutput};
+use gl_api::error::GlResult;
+use gl_api::shader::binary::ProgramCache;
+use gl_api::shader::preprocess::Defines;
 use gl_api::shader::program::*;
 use gl_api::shader::shader::*;
+use gl_api::shader::PipelineError;
+use std::path::{Path, PathBuf};
 
 const MAP_WIDTH: usize = 40;
 const MAP_HEIGHT: usize = 40;
@@ -83,7 +112,7 @@ pub struct Dirty;
 use specs::prelude::*;
 
 impl Component for TilePos {
-    type Storage = VecStorage<Self>;
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
 }
 
 impl Component for TerrainSprite {
@@ -109,32 +138,56 @@ vertex! {
     }
 }
 
-struct WorldUniforms {
-    // time: Uniform<f32>,
-    // scale: Uniform<f32>,
-    tile_amounts: Uniform<Vector2<i32>>,
-    tilemap: Uniform<Texture2D>,
-    positions: ShaderStorageBuffer<Vector2<f32>>,
-    uvs: ShaderStorageBuffer<Vector2<f32>>,
-    fg_colors: ShaderStorageBuffer<Vector4<f32>>,
-    bg_colors: ShaderStorageBuffer<Vector4<f32>>,
+// Has to match `TileInstance` in `world_ssbo.glslv`.
+std430! {
+    struct TileInstance {
+        pos: Vector2<f32>,
+        uv: Vector2<f32>,
+        fg: Vector4<f32>,
+        bg: Vector4<f32>,
+        visibility: u32,
+    }
+}
+
+impl<'a> From<&'a Cell> for TileInstance {
+    fn from(cell: &'a Cell) -> Self {
+        TileInstance {
+            // UNWRAP: map coordinates are nowhere near big enough to not fit
+            pos: cell.pos.cast().unwrap(),
+            uv: cell.sprite,
+            fg: cell.fg,
+            bg: cell.bg,
+            visibility: cell.visibility as u32,
+        }
+    }
+}
+
+program_env! {
+    env WorldUniforms {
+        // time: Uniform<f32>,
+        // scale: Uniform<f32>,
+        tile_amounts: Uniform<Vector2<i32>>,
+        tilemap: Uniform<Texture2D>,
+        instances: StorageBuffer<TileInstance>,
+    }
 }
 
 use std::collections::HashMap;
 
 struct WorldRenderer {
-    program: Program<Vector2<f32>, WorldUniforms>,
+    program: HotProgram<Vector2<f32>, WorldUniforms>,
     vao: VertexArray,
     vbo: VertexBuffer<Vector2<f32>>,
-    pos_to_index: HashMap<Vector2<usize>, usize>,
     tilemap: Texture2D,
+    generation: Option<u64>,
+    instances: usize,
     time: f32,
 }
 
 impl WorldRenderer {
-    pub fn new(mut program: Program<Vector2<f32>, WorldUniforms>, tilemap: Texture2D) -> Self {
-        let mut vao = VertexArray::new();
-        let mut vbo = VertexBuffer::new();
+    pub fn new(program: HotProgram<Vector2<f32>, WorldUniforms>, tilemap: Texture2D) -> GlResult<Self> {
+        let mut vao = VertexArray::new()?;
+        let mut vbo = VertexBuffer::new()?;
         vbo.upload(
             &[
                 Vector2::new(0.0, 0.0),
@@ -145,93 +198,116 @@ impl WorldRenderer {
                 Vector2::new(1.0, 0.0),
             ],
             UsageType::StaticDraw,
-        ).unwrap();
-        vao.add_buffer(&vbo).unwrap();
-        WorldRenderer {
+        )?;
+        vao.add_buffer(&vbo)?;
+        vao.set_label("world quad")?;
+        vbo.set_label("world quad")?;
+        let mut renderer = WorldRenderer {
             program,
             vao,
             vbo,
             tilemap,
-            pos_to_index: HashMap::new(),
+            generation: None,
+            instances: 0,
             time: 0.0,
-        }
+        };
+        renderer.program_changed()?;
+        Ok(renderer)
     }
-}
 
-impl<'a> System<'a> for WorldRenderer {
-    type SystemData = (
-        Read<'a, NewTerrain>,
-        Read<'a, ModifiedTerrain>,
-        ReadStorage<'a, Terrain>,
-        ReadStorage<'a, TerrainColor>,
-        ReadStorage<'a, TerrainSprite>,
-    );
-    fn run(&mut self, (new, modified, pos, color, sprite): Self::SystemData) {
-        let &ModifiedTerrain((was_modified, ref modified_set)) = &*modified;
-        let &NewTerrain((was_new, ref new_set)) = &*new;
-        let env = self.program.env_mut();
+    // A new program comes with its own instance buffer, which starts out empty,
+    // so everything has to be uploaded again.
+    fn program_changed(&mut self) -> GlResult<()> {
+        self.generation = None;
+        self.instances = 0;
+        // Uniforms go to whichever program is bound
+        self.program.get().bind()?;
+        let env = self.program.get_mut().env_mut();
+        env.tilemap.set(&self.tilemap)?;
+        env.instances.set_label("tile instances")
+    }
+
+    fn draw(&mut self, frame: &Frame) -> Result<(), gl_api::error::Error> {
+        // The post passes leave their own program bound
+        self.program.get().bind()?;
+        let env = self.program.get_mut().env_mut();
 
         // env.offset.set(Vector2::new(0.0, 0.0));
         // env.scale.set(1.0);
-        env.tile_amounts.set(&Vector2::new(MAP_WIDTH as i32, MAP_HEIGHT as i32));
-
-        // IDEA: After passing some threshold, should we just re-upload the buffers?
-        // New tiles, reupload all the GPU buffers with the new expanded data set
-        if was_new {
-            let positions = pos.join().map(|&Terrain(val)| val.cast().unwrap()).collect::<Vec<Vector2<f32>>>();
-            let fgs = color.join().map(|&TerrainColor { fg, .. }| fg).collect::<Vec<_>>();
-            let bgs = color.join().map(|&TerrainColor { bg, .. }| bg).collect::<Vec<_>>();
-            let sprites = sprite.join().map(|&TerrainSprite(val)| val.sprite()).collect::<Vec<_>>();
-
-            self.pos_to_index.clear();
-
-            // Update the pos -> buffer index map; we use this for updating tiles
-            for (index, &pos) in positions.iter().enumerate() {
-                self.pos_to_index.insert(pos.cast().unwrap(), index);
+        env.tile_amounts.set(&Vector2::new(frame.width as i32, frame.height as i32))?;
+
+        // Every cell is one instance, in draw order, so later layers end up on
+        // top.
+        if self.generation != Some(frame.generation) {
+            let count = frame.cells().count();
+
+            // Same amount of cells as last time, so we can just write over the
+            // old buffer. If that doesn't work out, or the driver lost what was
+            // in it, everything gets uploaded again.
+            // IDEA: After passing some threshold, should we just re-upload the buffer?
+            if count != self.instances || write_instances(&mut env.instances, frame).is_err() {
+                let instances = frame.cells().map(TileInstance::from).collect::<Vec<_>>();
+                env.instances.upload(&instances, UsageType::DynamicDraw)?;
+                self.instances = count;
             }
 
-            env.positions.upload(&*positions, UsageType::DynamicDraw).unwrap();
-            env.fg_colors.upload(&*fgs, UsageType::DynamicDraw).unwrap();
-            env.bg_colors.upload(&*bgs, UsageType::DynamicDraw).unwrap();
-            env.uvs.upload(&*sprites, UsageType::DynamicDraw).unwrap();
-        }
-
-        // Modified data, we gotta map the buffer and update everything.
-        if was_modified {
-            let mut positions = env.positions.map_mut().unwrap().unwrap();
-            let mut fg_colors = env.fg_colors.map_mut().unwrap().unwrap();
-            let mut bg_colors = env.bg_colors.map_mut().unwrap().unwrap();
-
-            for (&Terrain(pos), &TerrainColor { fg, bg }) in (&pos, &color).join() {
-                let idx = self.pos_to_index[&pos];
-                positions[idx] = pos.cast().unwrap();
-                fg_colors[idx] = fg;
-                bg_colors[idx] = bg;
-            }
+            self.generation = Some(frame.generation);
         }
 
         // TODO: cleaner rendering solution (aka cleaned up draw calls)
         unsafe {
-            self.vao.bind();
-            self.vbo.bind();
-            self.program.bind();
-            self.tilemap.bind();
+            self.vao.bind()?;
+            self.vbo.bind()?;
+            self.tilemap.bind()?;
             gl::Clear(gl::COLOR_BUFFER_BIT);
             gl_call!(DrawArraysInstanced(
                 gl::TRIANGLES,
                 0,
                 self.vbo.len() as i32,
-                (MAP_WIDTH * MAP_HEIGHT) as i32
-            )).unwrap();
+                self.instances as i32
+            ))?;
         }
 
         self.time += 0.01;
+        Ok(())
+    }
+}
+
+// Writes over the instances that are already in `buffer`.
+fn write_instances(buffer: &mut StorageBuffer<TileInstance>, frame: &Frame) -> Result<(), BufferMapError> {
+    let mut instances = buffer.map_mut()?;
+    for (idx, cell) in frame.cells().enumerate() {
+        instances.set(idx, &TileInstance::from(cell));
+    }
+    instances.unmap()
+}
+
+impl Renderer for WorldRenderer {
+    fn reload(&mut self, changed: &[PathBuf]) {
+        if self.program.reload(changed) {
+            if let Err(err) = self.program_changed() {
+                eprintln!("couldn't set up the new world shaders: {}", err);
+            }
+        }
+        if changed.iter().any(|path| path == Path::new(TILESET)) {
+            if let Err(err) = load_tilemap(&self.tilemap) {
+                eprintln!("couldn't reload the tileset: {}", err);
+            }
+        }
+    }
+
+    fn render(&mut self, frame: &Frame) {
+        let _group = debug::group("world");
+        if let Err(err) = self.draw(frame) {
+            eprintln!("couldn't draw the world: {}", err);
+        }
     }
 }
 
 struct GridTracker {
     new_id: ReaderId<InsertedFlag>,
     modified_id: ReaderId<ModifiedFlag>,
+    sprite_modified_id: ReaderId<ModifiedFlag>,
 }
 
 impl<'a> System<'a> for GridTracker {
@@ -242,9 +318,10 @@ impl<'a> System<'a> for GridTracker {
         Entities<'a>,
         ReadStorage<'a, Terrain>,
         ReadStorage<'a, TerrainColor>,
+        ReadStorage<'a, TerrainSprite>,
     );
 
-    fn run(&mut self, (mut new, mut modified, mut grid, entities, pos, colors): Self::SystemData) {
+    fn run(&mut self, (mut new, mut modified, mut grid, entities, pos, colors, sprites): Self::SystemData) {
         (new.0).1.clear();
         (modified.0).1.clear();
 
@@ -258,6 +335,10 @@ impl<'a> System<'a> for GridTracker {
         (modified.0).0 = modified_iter.len() > 0;
         (modified.0).1.extend(modified_iter.map(|item| *item.as_ref()));
 
+        let sprite_modified_iter = sprites.modified().read(&mut self.sprite_modified_id);
+        (modified.0).0 |= sprite_modified_iter.len() > 0;
+        (modified.0).1.extend(sprite_modified_iter.map(|item| *item.as_ref()));
+
         for (entity, &Terrain(pos)) in (&*entities, &pos).join() {
             let (width, height) = grid.dimensions();
             if pos.x < width && pos.y < height {
@@ -284,90 +365,50 @@ impl<'a> System<'a> for TileDemoSystem {
     }
 }
 
-fn place_room(world: &mut World, p1: Vector2<usize>, p2: Vector2<usize>) {
-    use std::cmp::{min, max};
-    use tiles::*;
-    let (min_x, min_y) = (min(p1.x, p2.x), min(p1.y, p2.y));
-    let (max_x, max_y) = (max(p1.x, p2.x), max(p1.y, p2.y));
-    let entity_grid = world.res.fetch::<TileGrid>();
-    let mut sprites = world.write_storage::<TerrainSprite>();
-
-    let mut set_border = |x, y, border| {
-        let tile = sprites.get_mut(entity_grid[Vector2::new(x, y)]).unwrap();
-        println!("{:?}", tile);
-        if let Some(prev) = tile.as_border() {
-            let new = prev | border;
-            if border == BORDER_BEND_BOTTOM_LEFT || prev == BORDER_BEND_BOTTOM_LEFT { print!("BOTTOM LEFT "); }
-            if border == BORDER_BEND_BOTTOM_RIGHT || prev == BORDER_BEND_BOTTOM_RIGHT { print!("BOTTOM RIGHT "); }
-            if border == BORDER_BEND_TOP_LEFT || prev == BORDER_BEND_TOP_LEFT { print!("TOP LEFT "); }
-            if border == BORDER_BEND_TOP_RIGHT || prev == BORDER_BEND_TOP_RIGHT { print!("TOP RIGHT "); }
-            println!("OVERLAP: ({}, {}) {:?} -> {:?}", x, y, prev, new);
-            *tile = TerrainSprite(Tile::Border(new));
-        } else {
-            *tile = TerrainSprite(Tile::Border(border));
-            println!("PLACE: ({}, {}) {:?}", x, y, border);
-        }
-        // let border = tile.as_border().unwrap_or(BorderTile::empty()) | border;
-    };
-
-    for x in min(min_x + 1, max_x)..max_x {
-        // Bottom and top
-        set_border(x, min_y, BORDER_VERTICAL);
-        set_border(x, max_y, BORDER_VERTICAL);
-    }
+/// Where the world gets drawn.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Backend {
+    OpenGl,
+    /// No window at all, just colored text on stdout. Handy over ssh or on
+    /// machines without a GPU.
+    Terminal,
+    /// Draw the first frame into a PNG on the CPU and quit.
+    Screenshot(String),
+}
 
-    for y in min(min_y + 1, max_y)..max_y {
-        // Left and right
-        set_border(min_x, y, BORDER_HORIZONTAL);
-        set_border(max_x, y, BORDER_HORIZONTAL);
-    }
+const USAGE: &str = "usage: birblike [SEED] [--terminal | --screenshot FILE] [--gl-debug] [--program-cache]";
 
-    set_border(min_x, min_y, BORDER_BEND_BOTTOM_LEFT);
-    set_border(min_x, max_y, BORDER_BEND_TOP_LEFT);
-    set_border(max_x, min_y, BORDER_BEND_BOTTOM_RIGHT);
-    set_border(max_x, max_y, BORDER_BEND_TOP_RIGHT);
+fn usage(problem: &str) -> ! {
+    eprintln!("{}\n{}", problem, USAGE);
+    ::std::process::exit(2)
 }
 
 fn main() {
-    let mut events_loop = glutin::EventsLoop::new();
-    let window = glutin::WindowBuilder::new()
-        .with_title("Birblike")
-        .with_dimensions(1000, 1000);
-    let context = glutin::ContextBuilder::new()
-        .with_gl(GlRequest::Specific(Api::OpenGl, (4, 3)))
-        .with_vsync(true);
-    let gl_window = glutin::GlWindow::new(window, context, &events_loop).unwrap();
-
-    unsafe {
-        gl_window.make_current().unwrap();
-        gl::load_with(|symbol| gl_window.get_proc_address(symbol) as *const _);
-        gl::ClearColor(0.5, 0.5, 0.5, 1.0);
-        // gl::Enable(gl::BLEND);
-        // gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+    // Pass a seed on the command line to get the same map again, and
+    // `--terminal` to play without a window. `--gl-debug` asks for a debug
+    // context and prints out whatever the driver complains about, and
+    // `--program-cache` keeps linked shaders around between runs.
+    let mut backend = Backend::OpenGl;
+    let mut gl_debug = false;
+    let mut program_cache = false;
+    let mut seed = None;
+    let mut args = ::std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &*arg {
+            "--terminal" => backend = Backend::Terminal,
+            "--gl-debug" => gl_debug = true,
+            "--program-cache" => program_cache = true,
+            "--screenshot" => match args.next() {
+                Some(path) => backend = Backend::Screenshot(path),
+                None => usage("--screenshot needs a file to write to"),
+            },
+            _ => match arg.parse() {
+                Ok(number) => seed = Some(number),
+                Err(_) => usage(&format!("unknown argument `{}`", arg)),
+            },
+        }
     }
 
-    let vertex = Shader::new(ShaderType::Vertex).unwrap();
-    let fragment = Shader::new(ShaderType::Fragment).unwrap();
-
-    vertex.source_from_file("res/world_ssbo.glslv").unwrap();
-    fragment.source_from_file("res/world.glslf").unwrap();
-
-    let mut program: Program<Vector2<f32>, _> = ProgramBuilder::new(vertex, fragment)
-        .unwrap()
-        .build(|mut builder| {
-            Ok(WorldUniforms {
-                // time: builder.uniform("time")?,
-                // scale: builder.uniform("scale")?,
-                tilemap: builder.uniform("tilemap")?,
-                tile_amounts: builder.uniform("tile_amounts")?,
-                uvs: builder.shader_storage("uvs")?,
-                positions: builder.shader_storage("positions")?,
-                fg_colors: builder.shader_storage("fg_colors")?,
-                bg_colors: builder.shader_storage("bg_colors")?,
-            })
-        })
-        .expect("blah");
-
     use specs::DispatcherBuilder;
 
     let mut world = World::new();
@@ -376,9 +417,20 @@ fn main() {
     world.register::<TerrainSprite>();
     world.register::<TerrainColor>();
     world.register::<Terrain>();
+    world.register::<TerrainKind>();
+    world.register::<Viewshed>();
+    world.register::<Revealed>();
+    world.register::<Observer>();
+    world.register::<Player>();
+    world.register::<ActorSprite>();
+    world.register::<Energy>();
+    world.register::<Speed>();
 
     let new_id = world.write_storage::<Terrain>().track_inserted();
     let modified_id = world.write_storage::<TerrainColor>().track_modified();
+    let sprite_modified_id = world.write_storage::<TerrainSprite>().track_modified();
+    let autotiler = Autotiler::new(&mut world);
+    let viewsheds = ViewshedSystem::new(&mut world);
 
     let mut rng = rand::thread_rng();
     let mut entity_refs = vec![];
@@ -387,6 +439,7 @@ fn main() {
             let entity = world
                 .create_entity()
                 .with(Terrain(Vector2::new(x, y)))
+                .with(TerrainKind::Wall)
                 .with(TerrainSprite(Tile::Air))
                 .with(TerrainColor {
                     fg: Vector4::new(1.0, 1.0, 1.0, 1.0),
@@ -399,65 +452,243 @@ fn main() {
 
     world.add_resource(ModifiedTerrain((false, BitSet::new())));
     world.add_resource(NewTerrain((false, BitSet::new())));
+    world.add_resource(FogOfWar::new(MAP_WIDTH, MAP_HEIGHT));
+    world.add_resource(ActionQueue::default());
+    world.add_resource(TurnScheduler::default());
     world.add_resource(TileGrid(GridX::from_iter(
         entity_refs,
         MAP_WIDTH,
         MAP_HEIGHT,
     )));
 
-    let texture = Texture2D::new();
-    texture.set_texture_bank(0);
-    let mut image = image::open("res/tileset.bmp").unwrap().flipv().to_rgba();
-    for (_, _, pixel) in image.enumerate_pixels_mut() {
-        if pixel.data == [255, 0, 255, 255] {
-            pixel.data = [0, 0, 0, 0];
-        }
-    }
-    texture.source(image::DynamicImage::ImageRgba8(image)).unwrap();
-    texture.mag_filter(MagnificationFilter::Nearest);
-    texture.min_filter(MinimizationFilter::Linear);
-    texture.texture_wrap_behavior(TextureAxis::S, WrapMode::Repeat);
-    texture.texture_wrap_behavior(TextureAxis::T, WrapMode::Repeat);
-
-    program.env_mut().tilemap.set(&texture);
-
-    let mut dispatcher = DispatcherBuilder::new()
-        .with(GridTracker { new_id, modified_id }, "track_grid", &[])
-        .with(TileDemoSystem, "demo", &["track_grid"])
-        .with_thread_local(WorldRenderer::new(program, texture))
+    let systems = DispatcherBuilder::new()
+        .with(autotiler, "autotile", &[])
+        .with(GridTracker { new_id, modified_id, sprite_modified_id }, "track_grid", &["autotile"])
+        .with(TurnSystem, "turn", &["track_grid"])
+        .with(PlayerMovement, "player_movement", &["turn"])
+        .with(viewsheds, "viewshed", &["player_movement"])
+        .with(FogOfWarSystem, "fog_of_war", &["viewshed"])
+        .with(TileDemoSystem, "demo", &["track_grid"]);
+
+    let seed = seed.unwrap_or_else(|| rng.gen());
+    println!("Map seed: {}", seed);
+
+    let dungeon = mapgen::generate(seed, MAP_WIDTH, MAP_HEIGHT, &Default::default());
+    mapgen::place_dungeon(&mut world, &dungeon);
+
+    world
+        .create_entity()
+        .with(TilePos(dungeon.rooms[0].center()))
+        .with(Viewshed::new(8))
+        .with(Revealed::default())
+        .with(Observer)
+        .with(Player)
+        .with(ActorSprite { glyph: b'@', fg: Vector4::new(1.0, 1.0, 1.0, 1.0) })
+        .with(Energy(ACTION_COST))
+        .with(Speed::default())
         .build();
 
-    // {
-    //     let mut rng = rand::thread_rng();
+    match backend {
+        Backend::OpenGl => run_opengl(world, systems, gl_debug, program_cache),
+        Backend::Terminal => run_terminal(world, systems),
+        Backend::Screenshot(path) => run_screenshot(world, systems, &path),
+    }
+}
 
-    //     for _ in 0..5 {
-    //         let x1 = rng.gen_range(0, MAP_WIDTH);
-    //         let x2 = rng.gen_range(0, MAP_WIDTH);
-    //         let y1 = rng.gen_range(0, MAP_HEIGHT);
-    //         let y2 = rng.gen_range(0, MAP_HEIGHT);
-    //         place_room(&mut world, Vector2::new(x1, y1), Vector2::new(x2, y2));
-    //     }
-    // }
+fn run_opengl(mut world: World, systems: specs::DispatcherBuilder, gl_debug: bool, program_cache: bool) {
+    let mut events_loop = glutin::EventsLoop::new();
+    let window = glutin::WindowBuilder::new()
+        .with_title("Birblike")
+        .with_dimensions(1000, 1000);
+    let context = glutin::ContextBuilder::new()
+        .with_gl(GlRequest::Specific(Api::OpenGl, (4, 3)))
+        .with_gl_debug_flag(gl_debug)
+        .with_vsync(true);
+    let gl_window = glutin::GlWindow::new(window, context, &events_loop).unwrap();
 
-    place_room(&mut world, Vector2::new(2, 2), Vector2::new(12, 12));
-    place_room(&mut world, Vector2::new(2, 4), Vector2::new(12, 14));
-    // place_room(&mut world, Vector2::new(4, 2), Vector2::new(14, 12));
-    // place_room(&mut world, Vector2::new(4, 4), Vector2::new(14, 14));
+    unsafe {
+        gl_window.make_current().unwrap();
+        gl::load_with(|symbol| gl_window.get_proc_address(symbol) as *const _);
+        gl::ClearColor(0.5, 0.5, 0.5, 1.0);
+        // gl::Enable(gl::BLEND);
+        // gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+    }
+    let _debug = if gl_debug { Some(DebugOutput::stderr().install().unwrap()) } else { None };
+
+    let (width, height) = gl_window.get_inner_size().unwrap_or((1000, 1000));
+    let cache = if program_cache { program_cache_dir().map(ProgramCache::new) } else { None };
+    let (renderer, mut post) = build_renderers(width, height, cache)
+        .unwrap_or_else(|err| panic!("couldn't set up rendering: {}", err));
+    for (i, pass) in post.passes().iter().enumerate() {
+        println!("F{}: toggle {}", i + 1, pass.name());
+    }
+
+    let mut dispatcher = systems
+        .with_thread_local(RenderSystem::new(renderer))
+        .build();
+
+    // Shaders and the tileset get reloaded whenever they're saved.
+    let mut watcher = AssetWatcher::new("res");
+    world.add_resource(ChangedAssets::default());
 
     let mut running = true;
     while running {
+        let changed = watcher.poll();
+        post.reload(&changed);
+        world.write_resource::<ChangedAssets>().0 = changed;
+
+        let mut resized = None;
         events_loop.poll_events(|event| match event {
             glutin::Event::WindowEvent { event, .. } => match event {
                 glutin::WindowEvent::CloseRequested => running = false,
-                glutin::WindowEvent::Resized(w, h) => gl_window.resize(w, h),
+                glutin::WindowEvent::Resized(w, h) => resized = Some((w, h)),
+                glutin::WindowEvent::KeyboardInput { input, .. } => {
+                    if let Some(action) = Action::from_key(&input) {
+                        world.write_resource::<ActionQueue>().push(action);
+                    } else if let Some(pass) = pass_toggle(&input).and_then(|i| post.passes_mut().get_mut(i)) {
+                        pass.enabled = !pass.enabled;
+                    }
+                }
                 _ => (),
             },
             _ => (),
         });
 
+        if let Some((w, h)) = resized {
+            gl_window.resize(w, h);
+            // Keeps drawing at the old size if the new one doesn't work out
+            if let Err(err) = post.resize(w, h) {
+                eprintln!("couldn't resize the post processing targets: {}", err);
+            }
+        }
 
+        if let Err(err) = post.begin() {
+            eprintln!("couldn't start post processing: {}", err);
+        }
         dispatcher.dispatch(&mut world.res);
         world.maintain();
+        if let Err(err) = post.present() {
+            eprintln!("couldn't run post processing: {}", err);
+        }
         gl_window.swap_buffers().unwrap();
     }
 }
+
+const TILESET: &str = "res/tileset.bmp";
+
+// Somewhere only the current user can write to, since whatever is in there
+// gets handed straight to the driver. Follows the XDG base directory spec.
+fn program_cache_dir() -> Option<PathBuf> {
+    let base = ::std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| ::std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(base.join("birblike").join("programs"))
+}
+
+// The world renderer, and the post processing it gets drawn through. With a
+// cache, linked programs get kept around between runs, so only shaders that
+// changed have to be compiled again.
+fn build_renderers(width: u32, height: u32, cache: Option<ProgramCache>) -> Result<(WorldRenderer, PostChain), gl_api::error::Error> {
+    let world_cache = cache.clone();
+    let program = HotProgram::new("the world shaders", move |defines| build_world_program(defines, world_cache.as_ref()))?;
+
+    let texture = Texture2D::new()?;
+    texture.set_label("tileset")?;
+    texture.set_texture_bank(0)?;
+    load_tilemap(&texture)?;
+    texture.mag_filter(MagnificationFilter::Nearest)?;
+    texture.min_filter(MinimizationFilter::Linear)?;
+    texture.texture_wrap_behavior(TextureAxis::S, WrapMode::Repeat)?;
+    texture.texture_wrap_behavior(TextureAxis::T, WrapMode::Repeat)?;
+
+    let mut post = PostChain::new(width, height)?;
+    for &(name, path) in &[("bloom", "res/post/bloom.glslf"), ("crt", "res/post/crt.glslf")] {
+        let mut pass = PostPass::from_file(name, path, cache.as_ref())?;
+        pass.enabled = false;
+        post.push(pass);
+    }
+
+    Ok((WorldRenderer::new(program, texture)?, post))
+}
+
+fn build_world_program(defines: &Defines, cache: Option<&ProgramCache>) -> Result<Program<Vector2<f32>, WorldUniforms>, PipelineError> {
+    let mut vertex = Shader::new(ShaderType::Vertex)?;
+    let mut fragment = Shader::new(ShaderType::Fragment)?;
+    vertex.source_from_file_with("res/world_ssbo.glslv", defines)?;
+    fragment.source_from_file_with("res/world.glslf", defines)?;
+
+    Ok(ProgramBuilder::new(vertex, fragment)?
+        .with_cache(cache)
+        .build(WorldUniforms::from_builder)?)
+}
+
+fn load_tilemap(texture: &Texture2D) -> Result<(), TextureError> {
+    let image = tiles::load_tileset(TILESET)?;
+    // GL puts the first row of a texture at the bottom.
+    texture.source(image::DynamicImage::ImageRgba8(image).flipv())
+}
+
+/// F1 toggles the first post processing pass, F2 the second, and so on.
+fn pass_toggle(input: &glutin::KeyboardInput) -> Option<usize> {
+    use glutin::VirtualKeyCode::*;
+
+    if input.state != glutin::ElementState::Pressed {
+        return None;
+    }
+    let keys = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+    let key = input.virtual_keycode?;
+    keys.iter().position(|&k| k == key)
+}
+
+fn run_terminal(mut world: World, systems: specs::DispatcherBuilder) {
+    use std::io::BufRead;
+
+    let mut dispatcher = systems
+        .with_thread_local(RenderSystem::new(TerminalRenderer::stdout()))
+        .build();
+
+    // Stdin is line buffered, so actions come in a line at a time. Everything
+    // on a line gets played out before the next one is read, and `q` or the
+    // end of input quits.
+    let stdin = ::std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        loop {
+            dispatcher.dispatch(&mut world.res);
+            world.maintain();
+            if world.read_resource::<TurnScheduler>().waiting_for_player() {
+                break;
+            }
+        }
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+        if line.trim() == "q" {
+            break;
+        }
+        let mut queue = world.write_resource::<ActionQueue>();
+        for action in line.chars().filter_map(Action::from_char) {
+            queue.push(action);
+        }
+    }
+}
+
+fn run_screenshot(mut world: World, systems: specs::DispatcherBuilder, path: &str) {
+    let mut dispatcher = systems.build();
+    dispatcher.dispatch(&mut world.res);
+    world.maintain();
+
+    let tileset = SoftwareRenderer::from_file(TILESET).unwrap_or_else(|err| {
+        eprintln!("couldn't load the tileset from {}: {}", TILESET, err);
+        ::std::process::exit(1)
+    });
+    let mut renderer = RenderSystem::new(tileset);
+    renderer.run_now(&world.res);
+    if let Err(err) = renderer.renderer().image().save(path) {
+        eprintln!("couldn't save the screenshot to {}: {}", path, err);
+        ::std::process::exit(1)
+    }
+}
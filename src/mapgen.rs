@@ -0,0 +1,336 @@
+use cgmath::Vector2;
+use grid::GridX;
+use rand::prng::XorShiftRng;
+use rand::{Rng, SeedableRng};
+use specs::prelude::*;
+use std::cmp::{max, min};
+use terrain::TerrainKind;
+use TileGrid;
+
+/// An axis-aligned rectangle of cells. Both corners are inclusive, so a room
+/// described by a `Rect` has its walls on the edges of the rectangle.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Rect {
+    pub min: Vector2<usize>,
+    pub max: Vector2<usize>,
+}
+
+impl Rect {
+    pub fn new(p1: Vector2<usize>, p2: Vector2<usize>) -> Self {
+        Rect {
+            min: Vector2::new(min(p1.x, p2.x), min(p1.y, p2.y)),
+            max: Vector2::new(max(p1.x, p2.x), max(p1.y, p2.y)),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.max.x - self.min.x + 1
+    }
+
+    pub fn height(&self) -> usize {
+        self.max.y - self.min.y + 1
+    }
+
+    pub fn center(&self) -> Vector2<usize> {
+        Vector2::new((self.min.x + self.max.x) / 2, (self.min.y + self.max.y) / 2)
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.min.x <= other.max.x && other.min.x <= self.max.x
+            && self.min.y <= other.max.y && other.min.y <= self.max.y
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct MapGenConfig {
+    /// Partitions are never split into pieces smaller than this on either
+    /// axis. Must be larger than `min_room_size`, since every room keeps one
+    /// cell of space between itself and the next partition.
+    pub min_leaf_size: usize,
+    /// Smallest room side length, walls included.
+    pub min_room_size: usize,
+    /// How many times the map may be recursively split.
+    pub max_depth: usize,
+}
+
+impl Default for MapGenConfig {
+    fn default() -> Self {
+        MapGenConfig {
+            min_leaf_size: 8,
+            min_room_size: 5,
+            max_depth: 5,
+        }
+    }
+}
+
+/// The layout of a generated map. This is just a description; use
+/// `place_dungeon` to actually put it into the world.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Dungeon {
+    pub width: usize,
+    pub height: usize,
+    pub rooms: Vec<Rect>,
+    /// Each corridor is the list of floor cells it occupies, going from the
+    /// center of one room to the center of another.
+    pub corridors: Vec<Vec<Vector2<usize>>>,
+}
+
+impl Dungeon {
+    /// Builds a grid that is `true` for every walkable cell in the dungeon.
+    pub fn floor_mask(&self) -> GridX<bool> {
+        let mut mask = GridX::from_iter(vec![false; self.width * self.height], self.width, self.height);
+        for room in &self.rooms {
+            for y in room.min.y + 1..room.max.y {
+                for x in room.min.x + 1..room.max.x {
+                    mask[Vector2::new(x, y)] = true;
+                }
+            }
+        }
+        for &cell in self.corridors.iter().flat_map(|corridor| corridor.iter()) {
+            mask[cell] = true;
+        }
+        mask
+    }
+}
+
+enum Node {
+    Leaf(Rect),
+    Split(Box<Node>, Box<Node>),
+}
+
+fn rng_from_seed(seed: u64) -> XorShiftRng {
+    let mut bytes = [0; 16];
+    for i in 0..8 {
+        bytes[i] = (seed >> (8 * i)) as u8;
+        // Fill the upper half too so that small seeds don't leave most of the
+        // generator's state zeroed.
+        bytes[i + 8] = (!seed >> (8 * i)) as u8;
+    }
+    XorShiftRng::from_seed(bytes)
+}
+
+/// Generates a dungeon layout for a `width` by `height` map. The same seed and
+/// config always produce the same layout.
+pub fn generate(seed: u64, width: usize, height: usize, config: &MapGenConfig) -> Dungeon {
+    assert!(config.min_leaf_size > config.min_room_size);
+    assert!(config.min_room_size >= 3);
+
+    let mut rng = rng_from_seed(seed);
+    let bounds = Rect::new(Vector2::new(0, 0), Vector2::new(width - 1, height - 1));
+    let tree = partition(&mut rng, bounds, config, 0);
+
+    let mut dungeon = Dungeon {
+        width,
+        height,
+        rooms: Vec::new(),
+        corridors: Vec::new(),
+    };
+    build_rooms(&mut rng, &tree, config, &mut dungeon);
+    dungeon
+}
+
+fn partition<R: Rng>(rng: &mut R, rect: Rect, config: &MapGenConfig, depth: usize) -> Node {
+    let (width, height) = (rect.width(), rect.height());
+    let can_split_x = width >= 2 * config.min_leaf_size;
+    let can_split_y = height >= 2 * config.min_leaf_size;
+
+    if depth >= config.max_depth || !(can_split_x || can_split_y) {
+        return Node::Leaf(rect);
+    }
+
+    // Prefer cutting across the long axis so we don't end up with a bunch of
+    // long, skinny partitions.
+    let split_x = match (can_split_x, can_split_y) {
+        (true, false) => true,
+        (false, true) => false,
+        _ if width * 4 > height * 5 => true,
+        _ if height * 4 > width * 5 => false,
+        _ => rng.gen(),
+    };
+
+    let (first, second) = if split_x {
+        let at = rect.min.x + rng.gen_range(config.min_leaf_size, width - config.min_leaf_size + 1);
+        (
+            Rect::new(rect.min, Vector2::new(at - 1, rect.max.y)),
+            Rect::new(Vector2::new(at, rect.min.y), rect.max),
+        )
+    } else {
+        let at = rect.min.y + rng.gen_range(config.min_leaf_size, height - config.min_leaf_size + 1);
+        (
+            Rect::new(rect.min, Vector2::new(rect.max.x, at - 1)),
+            Rect::new(Vector2::new(rect.min.x, at), rect.max),
+        )
+    };
+
+    Node::Split(
+        Box::new(partition(rng, first, config, depth + 1)),
+        Box::new(partition(rng, second, config, depth + 1)),
+    )
+}
+
+/// Places a room in every leaf under `node` and connects sibling subtrees with
+/// corridors. Returns the indices of the rooms that were placed in this
+/// subtree.
+fn build_rooms<R: Rng>(rng: &mut R, node: &Node, config: &MapGenConfig, dungeon: &mut Dungeon) -> Vec<usize> {
+    match *node {
+        Node::Leaf(leaf) => {
+            // Rooms never touch the max edges of their leaf, which guarantees at
+            // least one cell of space between rooms in neighboring leaves.
+            let (avail_w, avail_h) = (leaf.width() - 1, leaf.height() - 1);
+            if avail_w < config.min_room_size || avail_h < config.min_room_size {
+                return Vec::new();
+            }
+
+            let room_w = rng.gen_range(config.min_room_size, avail_w + 1);
+            let room_h = rng.gen_range(config.min_room_size, avail_h + 1);
+            let x = leaf.min.x + rng.gen_range(0, avail_w - room_w + 1);
+            let y = leaf.min.y + rng.gen_range(0, avail_h - room_h + 1);
+
+            dungeon.rooms.push(Rect::new(Vector2::new(x, y), Vector2::new(x + room_w - 1, y + room_h - 1)));
+            vec![dungeon.rooms.len() - 1]
+        }
+
+        Node::Split(ref first, ref second) => {
+            let mut first_rooms = build_rooms(rng, first, config, dungeon);
+            let second_rooms = build_rooms(rng, second, config, dungeon);
+
+            // Connect the closest pair of rooms across the split. Picking the
+            // closest pair keeps corridors short and makes it less likely that
+            // they plow through unrelated rooms.
+            let closest = first_rooms.iter()
+                .flat_map(|&a| second_rooms.iter().map(move |&b| (a, b)))
+                .min_by_key(|&(a, b)| {
+                    let (a, b) = (dungeon.rooms[a].center(), dungeon.rooms[b].center());
+                    distance(a.x, b.x) + distance(a.y, b.y)
+                });
+
+            if let Some((a, b)) = closest {
+                let (from, to) = (dungeon.rooms[a].center(), dungeon.rooms[b].center());
+                let horizontal_first = rng.gen();
+                dungeon.corridors.push(corridor_path(from, to, horizontal_first));
+            }
+
+            first_rooms.extend(second_rooms);
+            first_rooms
+        }
+    }
+}
+
+fn distance(a: usize, b: usize) -> usize {
+    max(a, b) - min(a, b)
+}
+
+fn step_towards(from: usize, to: usize) -> usize {
+    if from < to { from + 1 } else { from - 1 }
+}
+
+/// Lists the cells of an L-shaped path from `from` to `to`, inclusive on both
+/// ends. If the points share a row or column, the path is straight.
+fn corridor_path(from: Vector2<usize>, to: Vector2<usize>, horizontal_first: bool) -> Vec<Vector2<usize>> {
+    let mut path = vec![from];
+    let mut cur = from;
+
+    if horizontal_first {
+        while cur.x != to.x { cur.x = step_towards(cur.x, to.x); path.push(cur); }
+        while cur.y != to.y { cur.y = step_towards(cur.y, to.y); path.push(cur); }
+    } else {
+        while cur.y != to.y { cur.y = step_towards(cur.y, to.y); path.push(cur); }
+        while cur.x != to.x { cur.x = step_towards(cur.x, to.x); path.push(cur); }
+    }
+
+    path
+}
+
+/// Carves out the inside of a rectangular room with corners at `p1` and `p2`.
+/// The cells on the edge of the rectangle are left alone, so they end up as
+/// the room's walls unless something else has already dug through them.
+pub fn place_room(world: &mut World, p1: Vector2<usize>, p2: Vector2<usize>) {
+    let room = Rect::new(p1, p2);
+    let entity_grid = world.res.fetch::<TileGrid>();
+    let mut kinds = world.write_storage::<TerrainKind>();
+
+    for y in room.min.y + 1..room.max.y {
+        for x in room.min.x + 1..room.max.x {
+            *kinds.get_mut(entity_grid[Vector2::new(x, y)]).unwrap() = TerrainKind::Floor;
+        }
+    }
+}
+
+/// Carves the corridors of `dungeon` out of whatever is already in the world.
+/// The walls along them and the doorways where they enter rooms are taken care
+/// of by the `Autotiler`.
+pub fn carve_corridors(world: &mut World, dungeon: &Dungeon) {
+    let entity_grid = world.res.fetch::<TileGrid>();
+    let mut kinds = world.write_storage::<TerrainKind>();
+
+    for &cell in dungeon.corridors.iter().flat_map(|corridor| corridor.iter()) {
+        *kinds.get_mut(entity_grid[cell]).unwrap() = TerrainKind::Floor;
+    }
+}
+
+/// Puts a generated dungeon into the world's terrain.
+pub fn place_dungeon(world: &mut World, dungeon: &Dungeon) {
+    for room in &dungeon.rooms {
+        place_room(world, room.min, room.max);
+    }
+    carve_corridors(world, dungeon);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_map() {
+        let config = MapGenConfig::default();
+        assert_eq!(generate(1234, 40, 40, &config), generate(1234, 40, 40, &config));
+        assert_ne!(generate(1234, 40, 40, &config), generate(4321, 40, 40, &config));
+    }
+
+    #[test]
+    fn rooms_do_not_touch() {
+        let config = MapGenConfig::default();
+        for seed in 0..50 {
+            let dungeon = generate(seed, 60, 40, &config);
+            assert!(dungeon.rooms.len() > 1);
+            for (i, a) in dungeon.rooms.iter().enumerate() {
+                assert!(a.max.x < 60 && a.max.y < 40);
+                // Grow one room by a cell so that touching walls count too.
+                let grown = Rect::new(a.min, a.max + Vector2::new(1, 1));
+                for b in &dungeon.rooms[i + 1..] {
+                    assert!(!grown.intersects(b) && !b.intersects(&grown), "seed {}: {:?} touches {:?}", seed, a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn corridors_connect_all_rooms() {
+        let dungeon = generate(99, 40, 40, &MapGenConfig::default());
+        let floor = dungeon.floor_mask();
+
+        // Flood fill the floor from the first room and make sure we reach the
+        // rest of them.
+        let mut seen = GridX::from_iter(vec![false; 40 * 40], 40, 40);
+        let mut stack = vec![dungeon.rooms[0].center()];
+        while let Some(pos) = stack.pop() {
+            if seen[pos] || !floor[pos] { continue; }
+            seen[pos] = true;
+            // Floor never reaches the edge of the map, so this can't underflow.
+            stack.extend(&[pos + Vector2::new(1, 0), pos - Vector2::new(1, 0), pos + Vector2::new(0, 1), pos - Vector2::new(0, 1)]);
+        }
+
+        for room in &dungeon.rooms {
+            assert!(seen[room.center()], "{:?} is unreachable", room);
+        }
+    }
+
+    #[test]
+    fn straight_and_bent_paths() {
+        let path = corridor_path(Vector2::new(2, 2), Vector2::new(5, 2), true);
+        assert_eq!(path, vec![Vector2::new(2, 2), Vector2::new(3, 2), Vector2::new(4, 2), Vector2::new(5, 2)]);
+
+        let path = corridor_path(Vector2::new(2, 2), Vector2::new(3, 0), false);
+        assert_eq!(path, vec![Vector2::new(2, 2), Vector2::new(2, 1), Vector2::new(2, 0), Vector2::new(3, 0)]);
+    }
+}
@@ -0,0 +1,354 @@
+use cgmath::Vector2;
+use grid::{Grid, Orientation};
+use std::cmp::{max, min, Reverse};
+use std::collections::BinaryHeap;
+
+/// Whether diagonal moves may squeeze past impassable cells.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum DiagonalRule {
+    /// Diagonal moves are always allowed if the destination is passable.
+    Always,
+    /// Diagonal moves may cut one corner, but can't slip between two
+    /// impassable cells.
+    IfOneOpen,
+    /// Diagonal moves are only allowed if neither orthogonal cell next to the
+    /// move is impassable.
+    IfBothOpen,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Connectivity {
+    Four,
+    Eight(DiagonalRule),
+}
+
+const ORTHOGONAL: [(isize, isize); 4] = [(0, 1), (0, -1), (-1, 0), (1, 0)];
+const DIAGONAL: [(isize, isize); 4] = [(-1, 1), (1, 1), (-1, -1), (1, -1)];
+
+fn offset(pos: Vector2<usize>, (dx, dy): (isize, isize), (width, height): (usize, usize)) -> Option<Vector2<usize>> {
+    let (x, y) = (pos.x as isize + dx, pos.y as isize + dy);
+    if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height {
+        Some(Vector2::new(x as usize, y as usize))
+    } else {
+        None
+    }
+}
+
+/// Lists every cell that can be moved to from `pos` in one step. Out of bounds
+/// cells count as impassable.
+fn neighbors<P>(pos: Vector2<usize>, dims: (usize, usize), connectivity: Connectivity, passable: P) -> Vec<Vector2<usize>>
+where
+    P: Fn(Vector2<usize>) -> bool,
+{
+    let mut out = ORTHOGONAL.iter()
+        .filter_map(|&delta| offset(pos, delta, dims))
+        .filter(|&neighbor| passable(neighbor))
+        .collect::<Vec<_>>();
+
+    if let Connectivity::Eight(rule) = connectivity {
+        for &(dx, dy) in &DIAGONAL {
+            let target = match offset(pos, (dx, dy), dims) {
+                Some(target) if passable(target) => target,
+                _ => continue,
+            };
+
+            let open = |delta| offset(pos, delta, dims).map_or(false, |side| passable(side));
+            let (horizontal, vertical) = (open((dx, 0)), open((0, dy)));
+            let allowed = match rule {
+                DiagonalRule::Always => true,
+                DiagonalRule::IfOneOpen => horizontal || vertical,
+                DiagonalRule::IfBothOpen => horizontal && vertical,
+            };
+
+            if allowed {
+                out.push(target);
+            }
+        }
+    }
+
+    out
+}
+
+fn filled<T: Clone, O>(value: T, (width, height): (usize, usize)) -> Grid<T, O> {
+    Grid::from_iter(vec![value; width * height], width, height)
+}
+
+/// Finds the cheapest path from `start` to `goal`, including both ends. `cost`
+/// gives the cost of stepping onto a cell, or `None` if it can't be entered at
+/// all; costs must be at least 1. Diagonal steps cost the same as orthogonal
+/// ones.
+pub fn astar<T, O, F>(
+    grid: &Grid<T, O>,
+    start: Vector2<usize>,
+    goal: Vector2<usize>,
+    connectivity: Connectivity,
+    cost: F,
+) -> Option<Vec<Vector2<usize>>>
+where
+    O: Orientation,
+    F: Fn(Vector2<usize>, &T) -> Option<u32>,
+{
+    let dims = grid.dimensions();
+    if !grid.in_bounds(start) || !grid.in_bounds(goal) || cost(goal, &grid[goal]).is_none() {
+        return None;
+    }
+
+    // Our heuristic is the fewest steps it could possibly take to get to the
+    // goal, which never overestimates since every step costs at least 1.
+    let heuristic = |pos: Vector2<usize>| {
+        let dx = max(pos.x, goal.x) - min(pos.x, goal.x);
+        let dy = max(pos.y, goal.y) - min(pos.y, goal.y);
+        match connectivity {
+            Connectivity::Four => (dx + dy) as u32,
+            Connectivity::Eight(_) => max(dx, dy) as u32,
+        }
+    };
+    let passable = |pos: Vector2<usize>| cost(pos, &grid[pos]).is_some();
+
+    let mut best: Grid<Option<u32>, O> = filled(None, dims);
+    let mut came_from: Grid<Option<Vector2<usize>>, O> = filled(None, dims);
+    let mut open = BinaryHeap::new();
+
+    best[start] = Some(0);
+    open.push(Reverse((heuristic(start), 0, (start.x, start.y))));
+
+    while let Some(Reverse((_, so_far, (x, y)))) = open.pop() {
+        let current = Vector2::new(x, y);
+        if current == goal {
+            let mut path = vec![goal];
+            let mut cur = goal;
+            while let Some(prev) = came_from[cur] {
+                path.push(prev);
+                cur = prev;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        // Stale entry; we already found a cheaper way here.
+        if best[current].map_or(false, |best| so_far > best) {
+            continue;
+        }
+
+        for next in neighbors(current, dims, connectivity, &passable) {
+            let step = cost(next, &grid[next]).unwrap();
+            debug_assert!(step >= 1, "A* step costs must be at least 1");
+            let through = so_far + step;
+            if best[next].map_or(true, |best| through < best) {
+                best[next] = Some(through);
+                came_from[next] = Some(current);
+                open.push(Reverse((through + heuristic(next), through, (next.x, next.y))));
+            }
+        }
+    }
+
+    None
+}
+
+/// A grid of distances to the nearest goal, for things that want to "roll
+/// downhill" towards (or away from) something. See
+/// http://www.roguebasin.com/index.php?title=The_Incredible_Power_of_Dijkstra_Maps
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DijkstraMap<O> {
+    values: Grid<Option<i32>, O>,
+    connectivity: Connectivity,
+}
+
+impl<O: Orientation> DijkstraMap<O> {
+    /// Builds a map where every cell holds the cost of the cheapest path to
+    /// any of `sources`. Cells that can't reach a source hold `None`.
+    pub fn new<T, F>(grid: &Grid<T, O>, sources: &[Vector2<usize>], connectivity: Connectivity, cost: F) -> Self
+    where
+        F: Fn(Vector2<usize>, &T) -> Option<u32>,
+    {
+        let seeds = sources.iter().map(|&pos| (pos, 0));
+        Self::from_seeds(grid, seeds, connectivity, cost)
+    }
+
+    /// Builds a map from cells with arbitrary starting values. Each cell ends
+    /// up with the lowest value it can get by walking to a seed and adding up
+    /// the cost of the way there.
+    pub fn from_seeds<T, F, I>(grid: &Grid<T, O>, seeds: I, connectivity: Connectivity, cost: F) -> Self
+    where
+        F: Fn(Vector2<usize>, &T) -> Option<u32>,
+        I: IntoIterator<Item = (Vector2<usize>, i32)>,
+    {
+        let dims = grid.dimensions();
+        let passable = |pos: Vector2<usize>| cost(pos, &grid[pos]).is_some();
+        let mut values: Grid<Option<i32>, O> = filled(None, dims);
+        let mut open = BinaryHeap::new();
+
+        for (pos, value) in seeds {
+            if grid.in_bounds(pos) && values[pos].map_or(true, |old| value < old) {
+                values[pos] = Some(value);
+                open.push(Reverse((value, (pos.x, pos.y))));
+            }
+        }
+
+        // We're expanding outwards from the seeds, but the values are for
+        // walking back towards them, so the cost of going from `next` to
+        // `current` is the cost of entering `current`.
+        while let Some(Reverse((value, (x, y)))) = open.pop() {
+            let current = Vector2::new(x, y);
+            if values[current].map_or(false, |best| value > best) {
+                continue;
+            }
+
+            let step = match cost(current, &grid[current]) {
+                Some(step) => step as i32,
+                None => continue,
+            };
+
+            for next in neighbors(current, dims, connectivity, &passable) {
+                let through = value + step;
+                if values[next].map_or(true, |best| through < best) {
+                    values[next] = Some(through);
+                    open.push(Reverse((through, (next.x, next.y))));
+                }
+            }
+        }
+
+        DijkstraMap { values, connectivity }
+    }
+
+    pub fn get(&self, pos: Vector2<usize>) -> Option<i32> {
+        self.values[pos]
+    }
+
+    /// Makes a map for running away from whatever this map leads to. Rolling
+    /// downhill on the result moves away from the goals, but `bravery` lets
+    /// the fleer prefer escape routes that don't just run into a dead end;
+    /// values around 1.2 work well.
+    pub fn flee<T, F>(&self, grid: &Grid<T, O>, bravery: f32, cost: F) -> Self
+    where
+        F: Fn(Vector2<usize>, &T) -> Option<u32>,
+    {
+        let (width, height) = self.values.dimensions();
+        let mut seeds = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let pos = Vector2::new(x, y);
+                if let Some(value) = self.values[pos] {
+                    seeds.push((pos, -(value as f32 * bravery) as i32));
+                }
+            }
+        }
+        Self::from_seeds(grid, seeds, self.connectivity, cost)
+    }
+
+    /// Picks the neighbor of `pos` with the lowest value, if it's lower than
+    /// the value at `pos`.
+    pub fn downhill(&self, pos: Vector2<usize>) -> Option<Vector2<usize>> {
+        let here = self.values[pos]?;
+        let reachable = |pos: Vector2<usize>| self.values[pos].is_some();
+        neighbors(pos, self.values.dimensions(), self.connectivity, reachable)
+            .into_iter()
+            .filter_map(|next| self.values[next].map(|value| (value, next)))
+            .filter(|&(value, _)| value < here)
+            .min_by_key(|&(value, _)| value)
+            .map(|(_, next)| next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grid::GridX;
+
+    fn grid_from_str(rows: &[&str]) -> GridX<char> {
+        let (width, height) = (rows[0].len(), rows.len());
+        let cells = rows.iter().rev().flat_map(|row| row.chars()).collect::<Vec<_>>();
+        GridX::from_iter(cells, width, height)
+    }
+
+    fn cost(_: Vector2<usize>, &ch: &char) -> Option<u32> {
+        match ch {
+            '#' => None,
+            '~' => Some(5),
+            _ => Some(1),
+        }
+    }
+
+    #[test]
+    fn straight_line() {
+        let grid = grid_from_str(&["....."]);
+        let path = astar(&grid, Vector2::new(0, 0), Vector2::new(4, 0), Connectivity::Four, cost).unwrap();
+        assert_eq!(path.len(), 5);
+        assert_eq!(path[0], Vector2::new(0, 0));
+        assert_eq!(path[4], Vector2::new(4, 0));
+    }
+
+    #[test]
+    fn goes_around_walls() {
+        let grid = grid_from_str(&[
+            ".....",
+            ".###.",
+            "..#..",
+        ]);
+        let path = astar(&grid, Vector2::new(0, 0), Vector2::new(4, 0), Connectivity::Four, cost).unwrap();
+        assert_eq!(path.len(), 9);
+        assert!(path.iter().all(|&pos| grid[pos] != '#'));
+    }
+
+    #[test]
+    fn avoids_expensive_cells() {
+        let grid = grid_from_str(&[
+            ".....",
+            ".~~~.",
+        ]);
+        let path = astar(&grid, Vector2::new(0, 0), Vector2::new(4, 0), Connectivity::Four, cost).unwrap();
+        assert!(path.iter().all(|&pos| grid[pos] != '~'));
+    }
+
+    #[test]
+    fn unreachable_goal() {
+        let grid = grid_from_str(&["..#.."]);
+        assert_eq!(astar(&grid, Vector2::new(0, 0), Vector2::new(4, 0), Connectivity::Four, cost), None);
+        assert_eq!(astar(&grid, Vector2::new(0, 0), Vector2::new(2, 0), Connectivity::Four, cost), None);
+    }
+
+    #[test]
+    fn diagonal_corner_cutting() {
+        let grid = grid_from_str(&[
+            "#.",
+            ".#",
+        ]);
+        let (start, goal) = (Vector2::new(0, 0), Vector2::new(1, 1));
+        let always = Connectivity::Eight(DiagonalRule::Always);
+        assert_eq!(astar(&grid, start, goal, always, cost), Some(vec![start, goal]));
+        assert_eq!(astar(&grid, start, goal, Connectivity::Eight(DiagonalRule::IfOneOpen), cost), None);
+
+        let grid = grid_from_str(&[
+            "..",
+            ".#",
+        ]);
+        let one_open = Connectivity::Eight(DiagonalRule::IfOneOpen);
+        assert_eq!(astar(&grid, start, goal, one_open, cost), Some(vec![start, goal]));
+        let both_open = Connectivity::Eight(DiagonalRule::IfBothOpen);
+        assert_eq!(astar(&grid, start, goal, both_open, cost).map(|path| path.len()), Some(3));
+    }
+
+    #[test]
+    fn dijkstra_multiple_sources() {
+        let grid = grid_from_str(&[".......", "...#..."]);
+        let sources = [Vector2::new(0, 0), Vector2::new(6, 0)];
+        let map = DijkstraMap::new(&grid, &sources, Connectivity::Four, cost);
+        assert_eq!(map.get(Vector2::new(0, 0)), Some(0));
+        assert_eq!(map.get(Vector2::new(2, 0)), Some(2));
+        assert_eq!(map.get(Vector2::new(5, 1)), Some(2));
+        assert_eq!(map.get(Vector2::new(3, 0)), None);
+        assert_eq!(map.get(Vector2::new(3, 1)), Some(4));
+    }
+
+    #[test]
+    fn roll_downhill_and_flee() {
+        let grid = grid_from_str(&["......"]);
+        let map = DijkstraMap::new(&grid, &[Vector2::new(0, 0)], Connectivity::Four, cost);
+        assert_eq!(map.downhill(Vector2::new(3, 0)), Some(Vector2::new(2, 0)));
+        assert_eq!(map.downhill(Vector2::new(0, 0)), None);
+
+        let flee = map.flee(&grid, 1.2, cost);
+        assert_eq!(flee.downhill(Vector2::new(3, 0)), Some(Vector2::new(4, 0)));
+        assert_eq!(flee.downhill(Vector2::new(5, 0)), None);
+    }
+}
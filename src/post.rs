@@ -0,0 +1,220 @@
+use assets::HotProgram;
+use cgmath::Vector2;
+use gl;
+use gl_api::buffer::{UsageType, VertexBuffer};
+use gl_api::debug;
+use gl_api::error::{self, GlResult};
+use gl_api::framebuffer::{Attachment, Framebuffer, FramebufferBuilder, FramebufferError, FramebufferTarget};
+use gl_api::misc;
+use gl_api::render::{self, RenderTarget};
+use gl_api::shader::binary::ProgramCache;
+use gl_api::shader::preprocess::Defines;
+use gl_api::shader::program::{Program, ProgramBuilder};
+use std::path::PathBuf;
+use std::time::Instant;
+use gl_api::shader::shader::{Shader, ShaderType};
+use gl_api::shader::PipelineError;
+use gl_api::texture::{Texture2D, TextureFormat};
+use gl_api::uniform::Uniform;
+use gl_api::vertex_array::VertexArray;
+
+/// Every pass shares this vertex shader, which just covers the screen and hands
+/// `uv` to the fragment shader.
+const VERTEX_SHADER: &str = "res/post/post.glslv";
+
+program_env! {
+    env PassUniforms {
+        source: Uniform<Texture2D>,
+        // Passes don't have to use these, in which case they get optimized out.
+        resolution: Option<Uniform<Vector2<f32>>>,
+        time: Option<Uniform<f32>>,
+    }
+}
+
+/// A full-screen effect. The fragment shader gets the output of the previous
+/// pass as `source`, and can also ask for `resolution` and `time`.
+pub struct PostPass {
+    name: String,
+    program: HotProgram<Vector2<f32>, PassUniforms>,
+    pub enabled: bool,
+}
+
+fn build_pass(fragment_path: &str, defines: &Defines, cache: Option<&ProgramCache>) -> Result<Program<Vector2<f32>, PassUniforms>, PipelineError> {
+    let mut vertex = Shader::new(ShaderType::Vertex)?;
+    let mut fragment = Shader::new(ShaderType::Fragment)?;
+    vertex.source_from_file_with(VERTEX_SHADER, defines)?;
+    fragment.source_from_file_with(fragment_path, defines)?;
+
+    Ok(ProgramBuilder::new(vertex, fragment)?
+        .with_cache(cache)
+        .build(PassUniforms::from_builder)?)
+}
+
+impl PostPass {
+    pub fn from_file(name: &str, fragment_path: &str, cache: Option<&ProgramCache>) -> Result<Self, PipelineError> {
+        let (fragment_path, cache) = (fragment_path.to_owned(), cache.cloned());
+        let program = HotProgram::new(name, move |defines| build_pass(&fragment_path, defines, cache.as_ref()))?;
+        Ok(PostPass { name: name.into(), program, enabled: true })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+fn offscreen_target(width: u32, height: u32, label: &str) -> Result<RenderTarget, FramebufferError> {
+    let framebuffer = FramebufferBuilder::new(width, height)
+        .texture(Attachment::Color(0), TextureFormat::Rgba8)
+        .build()?;
+    if let Some(texture) = framebuffer.texture(Attachment::Color(0)) {
+        texture.set_label(label)?;
+    }
+    Ok(RenderTarget::Offscreen(framebuffer))
+}
+
+fn offscreen_targets(width: u32, height: u32) -> Result<[RenderTarget; 2], FramebufferError> {
+    Ok([offscreen_target(width, height, "post target 0")?, offscreen_target(width, height, "post target 1")?])
+}
+
+/// Renders the world offscreen, then runs it through a list of passes on the
+/// way to the window. Passes run in the order they were added, and can be
+/// turned on and off whenever.
+pub struct PostChain {
+    passes: Vec<PostPass>,
+    // The scene gets drawn into the first one, and then passes bounce back and
+    // forth between the two until the last one, which draws to the window.
+    targets: [RenderTarget; 2],
+    screen: RenderTarget,
+    vao: VertexArray,
+    vbo: VertexBuffer<Vector2<f32>>,
+    width: u32,
+    height: u32,
+    // When the chain was made, which is what `time` counts from.
+    started: Instant,
+}
+
+impl PostChain {
+    pub fn new(width: u32, height: u32) -> Result<Self, FramebufferError> {
+        let mut vao = VertexArray::new()?;
+        let mut vbo = VertexBuffer::new()?;
+        vbo.upload(
+            &[
+                Vector2::new(0.0, 0.0),
+                Vector2::new(0.0, 1.0),
+                Vector2::new(1.0, 1.0),
+                Vector2::new(0.0, 0.0),
+                Vector2::new(1.0, 1.0),
+                Vector2::new(1.0, 0.0),
+            ],
+            UsageType::StaticDraw,
+        )?;
+        vao.add_buffer(&vbo)?;
+        vao.set_label("post quad")?;
+        vbo.set_label("post quad")?;
+
+        Ok(PostChain {
+            passes: Vec::new(),
+            targets: offscreen_targets(width, height)?,
+            screen: RenderTarget::Default,
+            vao,
+            vbo,
+            width,
+            height,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn push(&mut self, pass: PostPass) {
+        self.passes.push(pass);
+    }
+
+    pub fn passes(&self) -> &[PostPass] {
+        &self.passes
+    }
+
+    pub fn passes_mut(&mut self) -> &mut [PostPass] {
+        &mut self.passes
+    }
+
+    pub fn pass_mut(&mut self, name: &str) -> Option<&mut PostPass> {
+        self.passes.iter_mut().find(|pass| pass.name == name)
+    }
+
+    /// The offscreen targets have to match the window, so this needs to be
+    /// called whenever it changes size.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        if (width, height) != (self.width, self.height) {
+            self.targets = offscreen_targets(width, height)?;
+            self.width = width;
+            self.height = height;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds any passes whose shaders changed.
+    pub fn reload(&mut self, changed: &[PathBuf]) {
+        for pass in &mut self.passes {
+            pass.program.reload(changed);
+        }
+    }
+
+    /// Sets things up so that anything drawn from now until `present` ends up
+    /// going through the passes.
+    pub fn begin(&self) -> GlResult<()> {
+        self.targets[0].bind()?;
+        misc::viewport(self.width, self.height);
+        Ok(())
+    }
+
+    /// Runs every enabled pass, and puts the result on the screen.
+    pub fn present(&mut self) -> Result<(), error::Error> {
+        let elapsed = self.started.elapsed();
+        let time = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 / 1e9;
+        let PostChain { ref mut passes, ref targets, ref screen, ref vao, ref vbo, width, height, .. } = *self;
+
+        let enabled = passes.iter_mut().filter(|pass| pass.enabled).collect::<Vec<_>>();
+        let count = enabled.len();
+        if count == 0 {
+            let _group = debug::group("blit");
+            // UNWRAP: the bounce targets are both offscreen
+            return Ok(Self::blit(targets[0].framebuffer().unwrap(), width, height)?);
+        }
+
+        let mut source = 0;
+        for (i, pass) in enabled.into_iter().enumerate() {
+            let _group = debug::group(&pass.name);
+            let target = if i + 1 == count { screen } else { &targets[1 - source] };
+            // UNWRAP: both of the bounce targets have a color texture
+            let texture = targets[source].framebuffer().and_then(|fb| fb.texture(Attachment::Color(0))).unwrap();
+
+            target.bind()?;
+            misc::viewport(width, height);
+            texture.set_texture_bank(0)?;
+            texture.bind()?;
+
+            pass.program.get().bind()?;
+            let env = pass.program.get_mut().env_mut();
+            env.source.set(texture)?;
+            if let Some(ref resolution) = env.resolution {
+                resolution.set(&Vector2::new(width as f32, height as f32))?;
+            }
+            if let Some(ref uniform) = env.time {
+                uniform.set(&time)?;
+            }
+
+            render::draw(vao, pass.program.get(), target, vbo.len())?;
+            source = 1 - source;
+        }
+        Ok(())
+    }
+
+    // With nothing to do, the scene gets copied straight to the window.
+    fn blit(scene: &Framebuffer, width: u32, height: u32) -> GlResult<()> {
+        scene.bind(FramebufferTarget::Read)?;
+        Framebuffer::bind_default(FramebufferTarget::Draw)?;
+        let (width, height) = (width as i32, height as i32);
+        unsafe {
+            gl_call!(BlitFramebuffer(0, 0, width, height, 0, 0, width, height, gl::COLOR_BUFFER_BIT, gl::NEAREST))
+        }
+    }
+}
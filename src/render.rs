@@ -0,0 +1,254 @@
+use assets::ChangedAssets;
+use cgmath::{Vector2, Vector4};
+use fog::{FogOfWar, Visibility};
+use specs::prelude::*;
+use specs::shred::PanicHandler;
+use std::path::PathBuf;
+use tiles::glyph_sprite;
+use {ModifiedTerrain, NewTerrain, Terrain, TerrainColor, TerrainSprite, TileGrid, TilePos};
+
+// What's behind an actor that isn't standing on any terrain.
+const NO_BACKGROUND: Vector4<f32> = Vector4 { x: 0.0, y: 0.0, z: 0.0, w: 1.0 };
+
+/// One tile's worth of stuff to draw.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Cell {
+    pub pos: Vector2<usize>,
+    /// Where the glyph is in the tileset, see `tiles::glyph_sprite`.
+    pub sprite: Vector2<f32>,
+    pub fg: Vector4<f32>,
+    pub bg: Vector4<f32>,
+    pub visibility: Visibility,
+}
+
+/// How something that moves around is drawn. Actors go on top of the terrain,
+/// with the terrain's background showing around the glyph.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ActorSprite {
+    /// A CP437 glyph, like `b'@'`.
+    pub glyph: u8,
+    pub fg: Vector4<f32>,
+}
+
+impl Component for ActorSprite {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Layer {
+    pub cells: Vec<Cell>,
+}
+
+/// Everything a backend needs to know to draw the world, without having to
+/// know anything about the ECS.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    /// Drawn in order, so cells in later layers cover up earlier ones.
+    pub layers: Vec<Layer>,
+    /// Goes up every time the contents of the frame change. Backends that keep
+    /// state around between frames can use this to tell when to refresh it.
+    pub generation: u64,
+}
+
+impl Frame {
+    /// Every cell in the frame, in draw order.
+    pub fn cells<'a>(&'a self) -> impl Iterator<Item = &'a Cell> + 'a {
+        self.layers.iter().flat_map(|layer| layer.cells.iter())
+    }
+}
+
+/// Something that can put a `Frame` on a screen (or anywhere else).
+pub trait Renderer {
+    /// Called once per dispatch, whether or not the frame changed.
+    fn render(&mut self, frame: &Frame);
+
+    /// Called before `render` with any files that changed on disk, for
+    /// backends that want to pick up new shaders or textures.
+    fn reload(&mut self, _changed: &[PathBuf]) {}
+}
+
+/// Builds a `Frame` out of the world whenever it changes, and hands it to a
+/// backend every frame.
+pub struct RenderSystem<R> {
+    renderer: R,
+    frame: Frame,
+}
+
+impl<R: Renderer> RenderSystem<R> {
+    pub fn new(renderer: R) -> Self {
+        RenderSystem { renderer, frame: Frame::default() }
+    }
+
+    pub fn renderer(&self) -> &R {
+        &self.renderer
+    }
+}
+
+impl<'a, R: Renderer> System<'a> for RenderSystem<R> {
+    type SystemData = (
+        Read<'a, NewTerrain>,
+        Read<'a, ModifiedTerrain>,
+        Read<'a, TileGrid, PanicHandler>,
+        Read<'a, FogOfWar, PanicHandler>,
+        ReadStorage<'a, Terrain>,
+        ReadStorage<'a, TerrainColor>,
+        ReadStorage<'a, TerrainSprite>,
+        ReadStorage<'a, TilePos>,
+        ReadStorage<'a, ActorSprite>,
+        Option<Read<'a, ChangedAssets>>,
+    );
+
+    fn run(&mut self, (new, modified, grid, fog, pos, color, sprite, actor_pos, actor_sprite, changed): Self::SystemData) {
+        if let Some(changed) = changed {
+            if !changed.0.is_empty() {
+                self.renderer.reload(&changed.0);
+            }
+        }
+
+        // Actors are cheap to gather up, so they get checked every time
+        // instead of tracking when they move. Only the ones in view show up.
+        let actors = (&actor_pos, &actor_sprite).join()
+            .filter(|&(pos, _)| grid.in_bounds(**pos) && fog.get(**pos) == Visibility::Visible)
+            .map(|(&TilePos(pos), actor)| Cell {
+                pos,
+                sprite: glyph_sprite(actor.glyph),
+                fg: actor.fg,
+                bg: color.get(grid[pos]).map_or(NO_BACKGROUND, |color| color.bg),
+                visibility: Visibility::Visible,
+            })
+            .collect::<Vec<_>>();
+        let actors_moved = self.frame.layers.get(1).map_or(true, |layer| layer.cells != actors);
+
+        if (new.0).0 || (modified.0).0 || fog.changed() || actors_moved {
+            let terrain = (&pos, &color, &sprite).join()
+                .map(|(&Terrain(pos), &TerrainColor { fg, bg }, &TerrainSprite(tile))| Cell {
+                    pos,
+                    sprite: tile.sprite(),
+                    fg,
+                    bg,
+                    visibility: fog.get(pos),
+                })
+                .collect();
+
+            let (width, height) = grid.dimensions();
+            self.frame = Frame {
+                width,
+                height,
+                layers: vec![Layer { cells: terrain }, Layer { cells: actors }],
+                generation: self.frame.generation + 1,
+            };
+        }
+
+        self.renderer.render(&self.frame);
+    }
+}
+
+/// A backend that doesn't draw anything, and just holds on to the last frame it
+/// was given. Good for tests.
+#[derive(Debug, Default)]
+pub struct FrameCapture {
+    pub last: Option<Frame>,
+    pub frames: usize,
+}
+
+impl Renderer for FrameCapture {
+    fn render(&mut self, frame: &Frame) {
+        self.frames += 1;
+        if self.last.as_ref().map_or(true, |last| last.generation != frame.generation) {
+            self.last = Some(frame.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fog::{FogOfWarSystem, Observer, Revealed};
+    use fov::Viewshed;
+    use grid::GridX;
+    use tiles::Tile;
+    use TilePos;
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Terrain>();
+        world.register::<TerrainColor>();
+        world.register::<TerrainSprite>();
+        world.register::<Observer>();
+        world.register::<Viewshed>();
+        world.register::<Revealed>();
+        world.register::<TilePos>();
+        world.register::<ActorSprite>();
+
+        let cells: Vec<_> = (0..2).map(|x| {
+            world.create_entity()
+                .with(Terrain(Vector2::new(x, 0)))
+                .with(TerrainSprite(Tile::Air))
+                .with(TerrainColor { fg: Vector4::new(1.0, 1.0, 1.0, 1.0), bg: Vector4::new(0.0, 0.0, 0.0, 1.0) })
+                .build()
+        }).collect();
+
+        world.add_resource(NewTerrain((true, BitSet::new())));
+        world.add_resource(ModifiedTerrain::default());
+        world.add_resource(TileGrid(GridX::from_iter(cells, 2, 1)));
+        world.add_resource(FogOfWar::new(2, 1));
+        world
+    }
+
+    #[test]
+    fn frame_is_only_rebuilt_on_change() {
+        let world = world();
+        let mut system = RenderSystem::new(FrameCapture::default());
+
+        system.run_now(&world.res);
+        {
+            let frame = system.renderer().last.as_ref().unwrap();
+            assert_eq!((frame.width, frame.height, frame.generation), (2, 1, 1));
+            assert_eq!(frame.cells().count(), 2);
+            assert!(frame.cells().all(|cell| cell.visibility == Visibility::Unseen));
+        }
+
+        // Settle the fog, and stop reporting the terrain as new.
+        FogOfWarSystem.run_now(&world.res);
+        (world.write_resource::<NewTerrain>().0).0 = false;
+
+        system.run_now(&world.res);
+        assert_eq!(system.renderer().frames, 2);
+        assert_eq!(system.renderer().last.as_ref().unwrap().generation, 1);
+    }
+
+    #[test]
+    fn actors_are_drawn_over_terrain() {
+        let mut world = world();
+        let mut viewshed = Viewshed::new(8);
+        viewshed.visible.extend(vec![Vector2::new(0, 0), Vector2::new(1, 0)]);
+        let player = world.create_entity()
+            .with(TilePos(Vector2::new(1, 0)))
+            .with(ActorSprite { glyph: b'@', fg: Vector4::new(1.0, 1.0, 0.0, 1.0) })
+            .with(viewshed)
+            .with(Revealed::default())
+            .with(Observer)
+            .build();
+        FogOfWarSystem.run_now(&world.res);
+
+        let mut system = RenderSystem::new(FrameCapture::default());
+        system.run_now(&world.res);
+        {
+            let frame = system.renderer().last.as_ref().unwrap();
+            let mut cells = frame.cells().filter(|cell| cell.pos == Vector2::new(1, 0));
+            let (terrain, top) = (cells.next().unwrap(), cells.next().unwrap());
+            assert_eq!(top.sprite, glyph_sprite(b'@'));
+            assert_eq!(top.bg, terrain.bg);
+        }
+
+        // Moving is enough to get a new frame, even if nothing else changed.
+        (world.write_resource::<NewTerrain>().0).0 = false;
+        *world.write_storage::<TilePos>().get_mut(player).unwrap() = TilePos(Vector2::new(0, 0));
+        system.run_now(&world.res);
+        let frame = system.renderer().last.as_ref().unwrap();
+        assert_eq!(frame.generation, 2);
+        assert_eq!(frame.layers[1].cells.iter().map(|cell| cell.pos).collect::<Vec<_>>(), vec![Vector2::new(0, 0)]);
+    }
+}
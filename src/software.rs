@@ -0,0 +1,177 @@
+use cgmath::{ElementWise, Vector4};
+use image::{ImageResult, Rgba, RgbaImage};
+use render::{Frame, Renderer};
+use std::path::Path;
+use tiles;
+
+// What `gl::ClearColor` is set to, for anywhere that doesn't have a cell.
+const CLEAR_COLOR: [u8; 4] = [128, 128, 128, 255];
+
+fn to_rgba(color: Vector4<f32>) -> Rgba<u8> {
+    let channel = |c: f32| (c.max(0.0).min(1.0) * 255.0).round() as u8;
+    Rgba { data: [channel(color.x), channel(color.y), channel(color.z), channel(color.w)] }
+}
+
+/// Draws frames on the CPU into an image, following the same rules as
+/// `world.glslf`. Mostly useful for checking what the map looks like in tests.
+pub struct SoftwareRenderer {
+    tileset: RgbaImage,
+    glyph_width: u32,
+    glyph_height: u32,
+    image: RgbaImage,
+    generation: Option<u64>,
+}
+
+impl SoftwareRenderer {
+    /// `tileset` should already be keyed, see `tiles::load_tileset`. It's
+    /// expected to be a 16x16 grid of glyphs.
+    pub fn new(tileset: RgbaImage) -> Self {
+        let (width, height) = tileset.dimensions();
+        SoftwareRenderer {
+            tileset,
+            glyph_width: width / 16,
+            glyph_height: height / 16,
+            image: RgbaImage::new(0, 0),
+            generation: None,
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        Ok(SoftwareRenderer::new(tiles::load_tileset(path)?))
+    }
+
+    /// The last frame that was drawn.
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    fn render(&mut self, frame: &Frame) {
+        if self.generation == Some(frame.generation) {
+            return;
+        }
+        self.generation = Some(frame.generation);
+
+        let (gw, gh) = (self.glyph_width, self.glyph_height);
+        let (width, height) = (frame.width as u32, frame.height as u32);
+        self.image = RgbaImage::from_pixel(width * gw, height * gh, Rgba { data: CLEAR_COLOR });
+
+        for cell in frame.cells() {
+            let (x, y) = (cell.pos.x as u32, cell.pos.y as u32);
+            if x >= width || y >= height {
+                continue;
+            }
+
+            // The tileset's rows go top to bottom, same as the image, but y
+            // points up in the world.
+            let (sprite_x, sprite_y) = (cell.sprite.x as u32 * gw, cell.sprite.y as u32 * gh);
+            let (out_x, out_y) = (x * gw, (height - 1 - y) * gh);
+
+            for py in 0..gh {
+                for px in 0..gw {
+                    let texel = self.tileset.get_pixel(sprite_x + px, sprite_y + py).data;
+                    let color = if texel[3] == 0 {
+                        cell.bg
+                    } else {
+                        let texel = Vector4::new(texel[0], texel[1], texel[2], texel[3]).map(|c| c as f32 / 255.0);
+                        texel.mul_element_wise(cell.fg)
+                    };
+                    self.image.put_pixel(out_x + px, out_y + py, to_rgba(cell.visibility.shade(color)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector2;
+    use fog::{FogOfWar, FogOfWarSystem, Observer, Revealed};
+    use fov::{Viewshed, ViewshedSystem};
+    use grid::GridX;
+    use image;
+    use render::{ActorSprite, RenderSystem};
+    use specs::prelude::*;
+    use std::env;
+    use terrain::{Autotiler, TerrainKind};
+    use tiles::Tile;
+    use {ModifiedTerrain, NewTerrain, Terrain, TerrainColor, TerrainSprite, TileGrid, TilePos};
+
+    // Compares against a checked in image. Run with `BLESS_GOLDEN=1` to write
+    // out what gets rendered instead, after checking that it looks right.
+    fn assert_golden(image: &RgbaImage, name: &str) {
+        let path = format!("res/golden/{}.png", name);
+        if env::var_os("BLESS_GOLDEN").is_some() {
+            image.save(&path).unwrap();
+            return;
+        }
+
+        let golden = image::open(&path).unwrap().to_rgba();
+        assert_eq!(golden.dimensions(), image.dimensions(), "{} changed size", path);
+        assert!(golden.into_raw() == image.clone().into_raw(), "{} doesn't match", path);
+    }
+
+    #[test]
+    fn room_with_fog() {
+        let rows = [
+            "##########",
+            "#....#...#",
+            "#....#...#",
+            "#........#",
+            "##########",
+        ];
+        let (width, height) = (rows[0].len(), rows.len());
+
+        let mut world = World::new();
+        world.register::<Terrain>();
+        world.register::<TerrainKind>();
+        world.register::<TerrainColor>();
+        world.register::<TerrainSprite>();
+        world.register::<TilePos>();
+        world.register::<ActorSprite>();
+        world.register::<Observer>();
+        world.register::<Viewshed>();
+        world.register::<Revealed>();
+        let mut autotiler = Autotiler::new(&mut world);
+        let mut viewsheds = ViewshedSystem::new(&mut world);
+
+        let mut cells = vec![];
+        for (y, row) in rows.iter().rev().enumerate() {
+            for (x, ch) in row.chars().enumerate() {
+                let entity = world.create_entity()
+                    .with(Terrain(Vector2::new(x, y)))
+                    .with(if ch == '#' { TerrainKind::Wall } else { TerrainKind::Floor })
+                    .with(TerrainSprite(Tile::Air))
+                    .with(TerrainColor { fg: Vector4::new(1.0, 0.8, 0.4, 1.0), bg: Vector4::new(0.1, 0.1, 0.2, 1.0) })
+                    .build();
+                cells.push(entity);
+            }
+        }
+        world.add_resource(NewTerrain((true, BitSet::new())));
+        world.add_resource(ModifiedTerrain::default());
+        world.add_resource(TileGrid(GridX::from_iter(cells, width, height)));
+        world.add_resource(FogOfWar::new(width, height));
+
+        // Look around the left side of the room, then walk over to the right so
+        // that some of it is only remembered.
+        let observer = world.create_entity()
+            .with(TilePos(Vector2::new(2, 2)))
+            .with(Viewshed::new(8))
+            .with(Revealed::default())
+            .with(Observer)
+            .build();
+        autotiler.run_now(&world.res);
+        viewsheds.run_now(&world.res);
+        FogOfWarSystem.run_now(&world.res);
+
+        *world.write_storage::<TilePos>().get_mut(observer).unwrap() = TilePos(Vector2::new(7, 1));
+        viewsheds.run_now(&world.res);
+        FogOfWarSystem.run_now(&world.res);
+
+        let mut system = RenderSystem::new(SoftwareRenderer::from_file("res/tileset.bmp").unwrap());
+        system.run_now(&world.res);
+        assert_golden(system.renderer().image(), "room_with_fog");
+    }
+}
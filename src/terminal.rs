@@ -0,0 +1,119 @@
+use cgmath::Vector4;
+use render::{Frame, Renderer};
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+use tiles::{glyph_char, sprite_glyph};
+
+fn to_rgb(color: Vector4<f32>) -> (u8, u8, u8) {
+    let channel = |c: f32| (c.max(0.0).min(1.0) * 255.0).round() as u8;
+    (channel(color.x), channel(color.y), channel(color.z))
+}
+
+/// Draws the map to a terminal with 24-bit ANSI colors instead of going through
+/// OpenGL. The whole map gets redrawn in place whenever anything about it
+/// changes.
+pub struct TerminalRenderer<W> {
+    out: W,
+    generation: Option<u64>,
+}
+
+impl TerminalRenderer<io::Stdout> {
+    pub fn stdout() -> Self {
+        TerminalRenderer::new(io::stdout())
+    }
+}
+
+impl<W: Write> TerminalRenderer<W> {
+    pub fn new(out: W) -> Self {
+        TerminalRenderer { out, generation: None }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> Renderer for TerminalRenderer<W> {
+    fn render(&mut self, frame: &Frame) {
+        if self.generation == Some(frame.generation) {
+            return;
+        }
+
+        let (width, height) = (frame.width, frame.height);
+        let mut cells = vec![None; width * height];
+        for cell in frame.cells() {
+            let pos = cell.pos;
+            if pos.x < width && pos.y < height {
+                let ch = glyph_char(sprite_glyph(cell.sprite));
+                let fg = to_rgb(cell.visibility.shade(cell.fg));
+                let bg = to_rgb(cell.visibility.shade(cell.bg));
+                cells[pos.y * width + pos.x] = Some((ch, fg, bg));
+            }
+        }
+
+        let mut out = String::new();
+        if self.generation.is_none() {
+            out.push_str("\x1b[2J");
+        }
+        out.push_str("\x1b[H");
+        self.generation = Some(frame.generation);
+
+        // y points up, but terminals draw top to bottom.
+        for y in (0..height).rev() {
+            for x in 0..width {
+                match cells[y * width + x] {
+                    Some((ch, (fr, fg, fb), (br, bg, bb))) => {
+                        let _ = write!(out, "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m{}", fr, fg, fb, br, bg, bb, ch);
+                    }
+                    None => out.push_str("\x1b[0m "),
+                }
+            }
+            out.push_str("\x1b[0m\n");
+        }
+
+        // There isn't much to do about a terminal that went away.
+        let _ = self.out.write_all(out.as_bytes());
+        let _ = self.out.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector2;
+    use fog::Visibility;
+    use render::{Cell, Layer};
+    use tiles::glyph_sprite;
+
+    fn frame(generation: u64) -> Frame {
+        let cell = |y, glyph, visibility| Cell {
+            pos: Vector2::new(0, y),
+            sprite: glyph_sprite(glyph),
+            fg: Vector4::new(1.0, 0.5, 0.0, 1.0),
+            bg: Vector4::new(0.0, 0.0, 1.0, 1.0),
+            visibility,
+        };
+        Frame {
+            width: 1,
+            height: 2,
+            layers: vec![Layer { cells: vec![cell(0, b'#', Visibility::Unseen), cell(1, b'@', Visibility::Visible)] }],
+            generation,
+        }
+    }
+
+    #[test]
+    fn draws_top_row_first() {
+        let mut renderer = TerminalRenderer::new(Vec::new());
+        renderer.render(&frame(1));
+        // Nothing changed, so nothing gets drawn
+        renderer.render(&frame(1));
+        renderer.render(&frame(2));
+
+        let rows = "\x1b[H\
+                    \x1b[38;2;255;128;0m\x1b[48;2;0;0;255m@\x1b[0m\n\
+                    \x1b[38;2;0;0;0m\x1b[48;2;0;0;0m#\x1b[0m\n";
+        // The screen only gets cleared the first time
+        let expected = format!("\x1b[2J{}{}", rows, rows);
+        assert_eq!(String::from_utf8(renderer.into_inner()).unwrap(), expected);
+    }
+}
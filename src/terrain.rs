@@ -0,0 +1,208 @@
+use cgmath::Vector2;
+use grid::GridX;
+use specs::prelude::*;
+use specs::shred::PanicHandler;
+use tiles::{BorderTile, LineStyle, Tile};
+use {Terrain, TerrainSprite, TileGrid};
+
+/// The structural layer of the map. This is the source of truth for what is a
+/// wall and what isn't; `TerrainSprite`s are derived from it by `Autotiler`, so
+/// carving out a room or door only ever means flipping cells to `Floor`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum TerrainKind {
+    Wall,
+    Floor,
+}
+
+impl TerrainKind {
+    pub fn is_solid(&self) -> bool {
+        *self == TerrainKind::Wall
+    }
+
+    /// Whether this blocks line of sight.
+    pub fn is_opaque(&self) -> bool {
+        *self == TerrainKind::Wall
+    }
+}
+
+impl Component for TerrainKind {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+fn offset(pos: Vector2<usize>, (dx, dy): (isize, isize), (width, height): (usize, usize)) -> Option<Vector2<usize>> {
+    let (x, y) = (pos.x as isize + dx, pos.y as isize + dy);
+    if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height {
+        Some(Vector2::new(x as usize, y as usize))
+    } else {
+        None
+    }
+}
+
+const SURROUNDING: [(isize, isize); 8] = [(0, 1), (0, -1), (-1, 0), (1, 0), (-1, 1), (1, 1), (-1, -1), (1, -1)];
+
+/// Whether the cell at `pos` should be drawn as a wall. Solid cells that aren't
+/// next to any floor are just bedrock, and are left blank.
+fn is_visible_wall<F>(pos: Vector2<usize>, dims: (usize, usize), is_solid: &F) -> bool
+where
+    F: Fn(Vector2<usize>) -> bool,
+{
+    is_solid(pos) && SURROUNDING.iter()
+        .filter_map(|&delta| offset(pos, delta, dims))
+        .any(|neighbor| !is_solid(neighbor))
+}
+
+/// Computes the tile for the cell at `pos` from the solidity of the cells
+/// around it. Walls join up with every visible wall directly next to them.
+pub fn derive_tile<F>(pos: Vector2<usize>, dims: (usize, usize), is_solid: F) -> Tile
+where
+    F: Fn(Vector2<usize>) -> bool,
+{
+    if !is_visible_wall(pos, dims, &is_solid) {
+        return Tile::Air;
+    }
+
+    let connects = |delta| offset(pos, delta, dims).map_or(false, |neighbor| is_visible_wall(neighbor, dims, &is_solid));
+    Tile::Border(BorderTile::from_connections(
+        connects((0, 1)),
+        connects((0, -1)),
+        connects((-1, 0)),
+        connects((1, 0)),
+        LineStyle::Double,
+    ))
+}
+
+/// Keeps `TerrainSprite`s in sync with `TerrainKind`s. Whenever a cell's kind
+/// is inserted or changed, every sprite that could depend on it is recomputed.
+pub struct Autotiler {
+    inserted_id: ReaderId<InsertedFlag>,
+    modified_id: ReaderId<ModifiedFlag>,
+    changed: BitSet,
+}
+
+impl Autotiler {
+    pub fn new(world: &mut World) -> Self {
+        let mut kinds = world.write_storage::<TerrainKind>();
+        Autotiler {
+            inserted_id: kinds.track_inserted(),
+            modified_id: kinds.track_modified(),
+            changed: BitSet::new(),
+        }
+    }
+}
+
+impl<'a> System<'a> for Autotiler {
+    type SystemData = (
+        Read<'a, TileGrid, PanicHandler>,
+        Entities<'a>,
+        ReadStorage<'a, Terrain>,
+        ReadStorage<'a, TerrainKind>,
+        WriteStorage<'a, TerrainSprite>,
+    );
+
+    fn run(&mut self, (grid, entities, positions, kinds, mut sprites): Self::SystemData) {
+        self.changed.clear();
+        let inserted_iter = kinds.inserted().read(&mut self.inserted_id);
+        let modified_iter = kinds.modified().read(&mut self.modified_id);
+        if inserted_iter.len() == 0 && modified_iter.len() == 0 {
+            return;
+        }
+        self.changed.extend(inserted_iter.map(|item| *item.as_ref()));
+        self.changed.extend(modified_iter.map(|item| *item.as_ref()));
+
+        // A sprite looks at its neighbors' visibility, which looks at *their*
+        // neighbors, so a change can affect anything up to two cells away.
+        let dims = grid.dimensions();
+        let mut dirty = GridX::from_iter(vec![false; dims.0 * dims.1], dims.0, dims.1);
+        for (_, &Terrain(pos), _) in (&*entities, &positions, &self.changed).join() {
+            for dy in -2..=2 {
+                for dx in -2..=2 {
+                    if let Some(neighbor) = offset(pos, (dx, dy), dims) {
+                        dirty[neighbor] = true;
+                    }
+                }
+            }
+        }
+
+        let is_solid = |pos| kinds.get(grid[pos]).map_or(true, TerrainKind::is_solid);
+        for y in 0..dims.1 {
+            for x in 0..dims.0 {
+                let pos = Vector2::new(x, y);
+                if !dirty[pos] {
+                    continue;
+                }
+
+                let tile = derive_tile(pos, dims, &is_solid);
+                // Sprites are flagged, so only touch the ones that actually
+                // changed to keep the renderer from re-uploading everything.
+                if let Some(sprite) = sprites.get(grid[pos]) {
+                    if **sprite == tile {
+                        continue;
+                    }
+                }
+                if let Some(sprite) = sprites.get_mut(grid[pos]) {
+                    *sprite = TerrainSprite(tile);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiles::*;
+
+    fn grid_from_str(rows: &[&str]) -> GridX<TerrainKind> {
+        // Rows are written top to bottom, but y points up.
+        let (width, height) = (rows[0].len(), rows.len());
+        let cells = rows.iter().rev()
+            .flat_map(|row| row.chars())
+            .map(|ch| if ch == '#' { TerrainKind::Wall } else { TerrainKind::Floor })
+            .collect::<Vec<_>>();
+        GridX::from_iter(cells, width, height)
+    }
+
+    fn tile_at(grid: &GridX<TerrainKind>, x: usize, y: usize) -> Tile {
+        derive_tile(Vector2::new(x, y), grid.dimensions(), |pos| grid[pos].is_solid())
+    }
+
+    #[test]
+    fn room_outline() {
+        let grid = grid_from_str(&[
+            "#######",
+            "#######",
+            "##...##",
+            "##...##",
+            "#######",
+            "#######",
+        ]);
+
+        assert_eq!(tile_at(&grid, 0, 0), Tile::Air);
+        assert_eq!(tile_at(&grid, 1, 1), Tile::Border(BORDER_BEND_BOTTOM_LEFT));
+        assert_eq!(tile_at(&grid, 5, 1), Tile::Border(BORDER_BEND_BOTTOM_RIGHT));
+        assert_eq!(tile_at(&grid, 1, 4), Tile::Border(BORDER_BEND_TOP_LEFT));
+        assert_eq!(tile_at(&grid, 5, 4), Tile::Border(BORDER_BEND_TOP_RIGHT));
+        assert_eq!(tile_at(&grid, 3, 1), Tile::Border(BORDER_HORIZONTAL));
+        assert_eq!(tile_at(&grid, 1, 2), Tile::Border(BORDER_VERTICAL));
+        assert_eq!(tile_at(&grid, 3, 2), Tile::Air);
+    }
+
+    #[test]
+    fn doorway_breaks_wall() {
+        let grid = grid_from_str(&[
+            "#######",
+            "##...##",
+            "##...##",
+            "###.###",
+            "###.###",
+            "#######",
+        ]);
+
+        // The wall on either side of the door now turns down into the
+        // corridor instead of continuing across.
+        assert_eq!(tile_at(&grid, 2, 2), Tile::Border(BORDER_BEND_TOP_RIGHT));
+        assert_eq!(tile_at(&grid, 4, 2), Tile::Border(BORDER_BEND_TOP_LEFT));
+        assert_eq!(tile_at(&grid, 1, 2), Tile::Border(BORDER_BEND_BOTTOM_LEFT));
+        assert_eq!(tile_at(&grid, 3, 2), Tile::Air);
+    }
+}
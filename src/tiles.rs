@@ -1,4 +1,6 @@
 use cgmath::Vector2;
+use image::{self, ImageResult, RgbaImage};
+use std::path::Path;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum Tile {
@@ -22,80 +24,221 @@ impl Tile {
 
 pub const SPRITE_AIR: Vector2<f32> = Vector2 { x: 0.0, y: 0.0 };
 
-pub const SPRITE_VERTICAL_BORDER: Vector2<f32> = Vector2 { x: 10.0, y: 11.0 };
-pub const SPRITE_HORIZONTAL_BORDER: Vector2<f32> = Vector2 { x: 13.0, y: 12.0 };
-pub const SPRITE_CROSS_BORDER: Vector2<f32> = Vector2 { x: 14.0, y: 12.0 };
+/// Gets the location of a CP437 glyph in the tileset. The tileset is laid out
+/// in code page order, 16 glyphs per row.
+pub fn glyph_sprite(glyph: u8) -> Vector2<f32> {
+    Vector2::new((glyph % 16) as f32, (glyph / 16) as f32)
+}
+
+/// Loads a tileset image. Magenta is the background color in the tileset, and
+/// gets keyed out to transparent so renderers can fill in the tile's own
+/// background there instead.
+pub fn load_tileset<P: AsRef<Path>>(path: P) -> ImageResult<RgbaImage> {
+    let mut image = image::open(path)?.to_rgba();
+    for (_, _, pixel) in image.enumerate_pixels_mut() {
+        if pixel.data == [255, 0, 255, 255] {
+            pixel.data = [0, 0, 0, 0];
+        }
+    }
+    Ok(image)
+}
+
+/// The inverse of `glyph_sprite`.
+pub fn sprite_glyph(sprite: Vector2<f32>) -> u8 {
+    (sprite.y as u8) * 16 + sprite.x as u8
+}
 
-pub const SPRITE_BEND_TOP_LEFT: Vector2<f32> = Vector2 { x: 9.0, y: 12.0 };
-pub const SPRITE_BEND_TOP_RIGHT: Vector2<f32> = Vector2 { x: 11.0, y: 11.0 };
-pub const SPRITE_BEND_BOTTOM_LEFT: Vector2<f32> = Vector2 { x: 8.0, y: 12.0 };
-pub const SPRITE_BEND_BOTTOM_RIGHT: Vector2<f32> = Vector2 { x: 12.0, y: 11.0 };
+/// Gets the Unicode character that looks like a CP437 glyph. Glyph 0 is blank
+/// in the tileset, so it maps to a space.
+pub fn glyph_char(glyph: u8) -> char {
+    CP437[glyph as usize]
+}
+
+const CP437: [char; 256] = [
+    ' ', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+    ' ', '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?',
+    '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O',
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', '\\', ']', '^', '_',
+    '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '⌂',
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
 
-pub const SPRITE_T_POINT_UP: Vector2<f32> = Vector2 { x: 10.0, y: 12.0 };
-pub const SPRITE_T_POINT_DOWN: Vector2<f32> = Vector2 { x: 11.0, y: 12.0 };
-pub const SPRITE_T_POINT_LEFT: Vector2<f32> = Vector2 { x: 9.0, y: 11.0 };
-pub const SPRITE_T_POINT_RIGHT: Vector2<f32> = Vector2 { x: 12.0, y: 12.0 };
+/// How a border is drawn in one direction. Ordered by weight, so combining two
+/// borders keeps the heavier line.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum LineStyle {
+    None,
+    Single,
+    Double,
+}
 
-pub const SPRITE_NUB_TOP: Vector2<f32> = Vector2 { x: 6.0, y: 13.0 };
-pub const SPRITE_NUB_BOTTOM: Vector2<f32> = Vector2 { x: 13.0, y: 11.0 };
-pub const SPRITE_NUB_LEFT: Vector2<f32> = Vector2 { x: 5.0, y: 13.0 };
-pub const SPRITE_NUB_RIGHT: Vector2<f32> = Vector2 { x: 14.0, y: 11.0 };
+impl Default for LineStyle {
+    fn default() -> Self { LineStyle::None }
+}
+
+impl ::std::ops::BitOr for LineStyle {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        ::std::cmp::max(self, rhs)
+    }
+}
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
 pub struct BorderTile {
-    top: bool,
-    bottom: bool,
-    left: bool,
-    right: bool,
+    top: LineStyle,
+    bottom: LineStyle,
+    left: LineStyle,
+    right: LineStyle,
 }
 
 impl BorderTile {
+    pub fn new(top: LineStyle, bottom: LineStyle, left: LineStyle, right: LineStyle) -> Self {
+        BorderTile { top, bottom, left, right }
+    }
+
+    /// Creates a border that connects in the given directions, all drawn with
+    /// the same style.
+    pub fn from_connections(top: bool, bottom: bool, left: bool, right: bool, style: LineStyle) -> Self {
+        let pick = |connected| if connected { style } else { LineStyle::None };
+        BorderTile::new(pick(top), pick(bottom), pick(left), pick(right))
+    }
+
     pub fn empty() -> Self {
-        BorderTile { top: false, bottom: false, left: false, right: false }
+        BorderTile::default()
     }
 
-    pub fn sprite(&self) -> Vector2<f32> {
-        match (self.top, self.bottom, self.left, self.right) {
-            (true,  false, true,  true ) => SPRITE_T_POINT_UP,
-            (false, true,  true,  true ) => SPRITE_T_POINT_DOWN,
-            (true,  true,  true,  false) => SPRITE_T_POINT_LEFT,
-            (true,  true,  false, true ) => SPRITE_T_POINT_RIGHT,
-            
-            (true,  false, false, false) => SPRITE_NUB_TOP,
-            (false, true,  false, false) => SPRITE_NUB_BOTTOM,
-            (false, false, true,  false) => SPRITE_NUB_LEFT,
-            (false, false, false, true ) => SPRITE_NUB_RIGHT,
-
-            (false, true,  false, true ) => SPRITE_BEND_TOP_LEFT,
-            (true,  false, false, true ) => SPRITE_BEND_BOTTOM_LEFT,
-            (false, true,  true,  false) => SPRITE_BEND_TOP_RIGHT,
-            (true,  false, true,  false) => SPRITE_BEND_BOTTOM_RIGHT,
-
-            (true,  true,  false, false) => SPRITE_HORIZONTAL_BORDER,
-            (false, false, true,  true ) => SPRITE_VERTICAL_BORDER,
-
-            (true,  true,  true,  true ) => SPRITE_CROSS_BORDER,
-            (false, false, false, false) => SPRITE_AIR,
+    /// Redraws every connection of this border in `style`.
+    pub fn with_style(self, style: LineStyle) -> Self {
+        BorderTile::from_connections(
+            self.top != LineStyle::None,
+            self.bottom != LineStyle::None,
+            self.left != LineStyle::None,
+            self.right != LineStyle::None,
+            style,
+        )
+    }
+
+    /// CP437 can only draw one style per axis, so if a line changes style as it
+    /// passes through this tile, the whole axis is drawn with the heavier one.
+    fn normalized(&self) -> Self {
+        let vertical = self.top | self.bottom;
+        let horizontal = self.left | self.right;
+        let restyle = |dir, style| if dir == LineStyle::None { dir } else { style };
+        BorderTile {
+            top: restyle(self.top, vertical),
+            bottom: restyle(self.bottom, vertical),
+            left: restyle(self.left, horizontal),
+            right: restyle(self.right, horizontal),
         }
     }
+
+    /// The CP437 box drawing glyph for this border.
+    pub fn glyph(&self) -> u8 {
+        const N: LineStyle = LineStyle::None;
+        const S: LineStyle = LineStyle::Single;
+        const D: LineStyle = LineStyle::Double;
+
+        let BorderTile { top, bottom, left, right } = self.normalized();
+        match (top, bottom, left, right) {
+            (N, N, N, N) => 0,
+
+            (S, S, N, N) | (S, N, N, N) | (N, S, N, N) => 179, // │
+            (N, N, S, S) | (N, N, S, N) | (N, N, N, S) => 196, // ─
+            (D, D, N, N) => 186, // ║
+            (N, N, D, D) => 205, // ═
+
+            // There aren't any glyphs for double dead ends, so these are the
+            // closest thing we've got.
+            (D, N, N, N) => 214,
+            (N, D, N, N) => 189,
+            (N, N, D, N) => 213,
+            (N, N, N, D) => 190,
+
+            (N, S, N, S) => 218, // ┌
+            (N, D, N, D) => 201, // ╔
+            (N, D, N, S) => 214, // ╓
+            (N, S, N, D) => 213, // ╒
+
+            (S, N, N, S) => 192, // └
+            (D, N, N, D) => 200, // ╚
+            (D, N, N, S) => 211, // ╙
+            (S, N, N, D) => 212, // ╘
+
+            (N, S, S, N) => 191, // ┐
+            (N, D, D, N) => 187, // ╗
+            (N, D, S, N) => 183, // ╖
+            (N, S, D, N) => 184, // ╕
+
+            (S, N, S, N) => 217, // ┘
+            (D, N, D, N) => 188, // ╝
+            (D, N, S, N) => 189, // ╜
+            (S, N, D, N) => 190, // ╛
+
+            (S, S, N, S) => 195, // ├
+            (D, D, N, D) => 204, // ╠
+            (D, D, N, S) => 199, // ╟
+            (S, S, N, D) => 198, // ╞
+
+            (S, S, S, N) => 180, // ┤
+            (D, D, D, N) => 185, // ╣
+            (D, D, S, N) => 182, // ╢
+            (S, S, D, N) => 181, // ╡
+
+            (S, N, S, S) => 193, // ┴
+            (D, N, D, D) => 202, // ╩
+            (D, N, S, S) => 208, // ╨
+            (S, N, D, D) => 207, // ╧
+
+            (N, S, S, S) => 194, // ┬
+            (N, D, D, D) => 203, // ╦
+            (N, D, S, S) => 210, // ╥
+            (N, S, D, D) => 209, // ╤
+
+            (S, S, S, S) => 197, // ┼
+            (D, D, D, D) => 206, // ╬
+            (D, D, S, S) => 215, // ╫
+            (S, S, D, D) => 216, // ╪
+
+            // `normalized` makes sure both ends of an axis match, so nothing
+            // else can show up here.
+            other => unreachable!("unnormalized border {:?}", other),
+        }
+    }
+
+    pub fn sprite(&self) -> Vector2<f32> {
+        glyph_sprite(self.glyph())
+    }
 }
 
-pub const BORDER_T_POINT_UP: BorderTile        = BorderTile { top: true,  bottom: false, left: true,  right: true  };
-pub const BORDER_T_POINT_DOWN: BorderTile      = BorderTile { top: false, bottom: true,  left: true,  right: true  };
-pub const BORDER_T_POINT_LEFT: BorderTile      = BorderTile { top: true,  bottom: true,  left: true,  right: false };
-pub const BORDER_T_POINT_RIGHT: BorderTile     = BorderTile { top: true,  bottom: true,  left: false, right: true  };
-pub const BORDER_NUB_TOP: BorderTile           = BorderTile { top: true,  bottom: false, left: false, right: false };
-pub const BORDER_NUB_BOTTOM: BorderTile        = BorderTile { top: false, bottom: true,  left: false, right: false };
-pub const BORDER_NUB_LEFT: BorderTile          = BorderTile { top: false, bottom: false, left: true,  right: false };
-pub const BORDER_NUB_RIGHT: BorderTile         = BorderTile { top: false, bottom: false, left: false, right: true  };
-pub const BORDER_BEND_TOP_LEFT: BorderTile     = BorderTile { top: false, bottom: true,  left: false, right: true  };
-pub const BORDER_BEND_BOTTOM_LEFT: BorderTile  = BorderTile { top: true,  bottom: false, left: false, right: true  };
-pub const BORDER_BEND_TOP_RIGHT: BorderTile    = BorderTile { top: false, bottom: true,  left: true,  right: false };
-pub const BORDER_BEND_BOTTOM_RIGHT: BorderTile = BorderTile { top: true,  bottom: false, left: true,  right: false };
-pub const BORDER_HORIZONTAL: BorderTile        = BorderTile { top: false, bottom: false, left: true,  right: true  };
-pub const BORDER_VERTICAL: BorderTile          = BorderTile { top: true,  bottom: true,  left: false, right: false };
-pub const BORDER_CROSS: BorderTile             = BorderTile { top: true,  bottom: true,  left: true,  right: true  };
-pub const BORDER_AIR: BorderTile               = BorderTile { top: false, bottom: false, left: false, right: false };
+const D: LineStyle = LineStyle::Double;
+const N: LineStyle = LineStyle::None;
+
+pub const BORDER_T_POINT_UP: BorderTile        = BorderTile { top: D, bottom: N, left: D, right: D };
+pub const BORDER_T_POINT_DOWN: BorderTile      = BorderTile { top: N, bottom: D, left: D, right: D };
+pub const BORDER_T_POINT_LEFT: BorderTile      = BorderTile { top: D, bottom: D, left: D, right: N };
+pub const BORDER_T_POINT_RIGHT: BorderTile     = BorderTile { top: D, bottom: D, left: N, right: D };
+pub const BORDER_NUB_TOP: BorderTile           = BorderTile { top: D, bottom: N, left: N, right: N };
+pub const BORDER_NUB_BOTTOM: BorderTile        = BorderTile { top: N, bottom: D, left: N, right: N };
+pub const BORDER_NUB_LEFT: BorderTile          = BorderTile { top: N, bottom: N, left: D, right: N };
+pub const BORDER_NUB_RIGHT: BorderTile         = BorderTile { top: N, bottom: N, left: N, right: D };
+pub const BORDER_BEND_TOP_LEFT: BorderTile     = BorderTile { top: N, bottom: D, left: N, right: D };
+pub const BORDER_BEND_BOTTOM_LEFT: BorderTile  = BorderTile { top: D, bottom: N, left: N, right: D };
+pub const BORDER_BEND_TOP_RIGHT: BorderTile    = BorderTile { top: N, bottom: D, left: D, right: N };
+pub const BORDER_BEND_BOTTOM_RIGHT: BorderTile = BorderTile { top: D, bottom: N, left: D, right: N };
+pub const BORDER_HORIZONTAL: BorderTile        = BorderTile { top: N, bottom: N, left: D, right: D };
+pub const BORDER_VERTICAL: BorderTile          = BorderTile { top: D, bottom: D, left: N, right: N };
+pub const BORDER_CROSS: BorderTile             = BorderTile { top: D, bottom: D, left: D, right: D };
+pub const BORDER_AIR: BorderTile               = BorderTile { top: N, bottom: N, left: N, right: N };
 
 #[cfg(test)]
 mod tests {
@@ -112,6 +255,42 @@ mod tests {
         assert_eq!(BORDER_BEND_BOTTOM_LEFT | BORDER_VERTICAL, BORDER_T_POINT_RIGHT);
         assert_eq!(BORDER_BEND_BOTTOM_RIGHT | BORDER_VERTICAL, BORDER_T_POINT_LEFT);
     }
+
+    #[test]
+    fn mixed_styles() {
+        let single_horizontal = BORDER_HORIZONTAL.with_style(LineStyle::Single);
+        assert_eq!((BORDER_VERTICAL | single_horizontal).glyph(), 215); // ╫
+        assert_eq!((BORDER_BEND_TOP_LEFT.with_style(LineStyle::Single) | BORDER_VERTICAL).glyph(), 199); // ╟
+        assert_eq!((single_horizontal | BORDER_HORIZONTAL).glyph(), 205); // ═
+
+        // A line that changes style partway through gets drawn with the
+        // heavier style on that axis.
+        let tile = BorderTile::new(LineStyle::Double, LineStyle::Single, LineStyle::None, LineStyle::Single);
+        assert_eq!(tile.glyph(), 199); // ╟
+    }
+
+    #[test]
+    fn every_combination_has_a_glyph() {
+        let styles = [LineStyle::None, LineStyle::Single, LineStyle::Double];
+        for &top in &styles {
+            for &bottom in &styles {
+                for &left in &styles {
+                    for &right in &styles {
+                        BorderTile::new(top, bottom, left, right).glyph();
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sprites_map_back_to_characters() {
+        let glyph = BORDER_T_POINT_DOWN.glyph();
+        assert_eq!(sprite_glyph(glyph_sprite(glyph)), glyph);
+        assert_eq!(glyph_char(glyph), '╦');
+        assert_eq!(glyph_char(sprite_glyph(Tile::Air.sprite())), ' ');
+        assert_eq!(glyph_char(b'@'), '@');
+    }
 }
 
 impl ::std::ops::BitOr for BorderTile {
@@ -0,0 +1,181 @@
+use input::{ActionQueue, Player};
+use specs::prelude::*;
+
+/// How much energy it takes to do anything.
+pub const ACTION_COST: i32 = 100;
+/// The speed of a perfectly ordinary actor, who gets one action per turn.
+pub const NORMAL_SPEED: i32 = 100;
+
+/// Built up every turn, and spent to take actions. Anything with at least
+/// `ACTION_COST` energy can act.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct Energy(pub i32);
+
+impl Component for Energy {
+    type Storage = VecStorage<Self>;
+}
+
+/// How much energy an actor gains every turn.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Speed(pub i32);
+
+impl Default for Speed {
+    fn default() -> Self { Speed(NORMAL_SPEED) }
+}
+
+impl Component for Speed {
+    type Storage = VecStorage<Self>;
+}
+
+/// Decides who gets to act this frame. Systems that carry out actions should
+/// check `is_ready` first, and take `ACTION_COST` out of the actor's `Energy`
+/// once they've acted.
+#[derive(Debug, Default)]
+pub struct TurnScheduler {
+    ready: Vec<Entity>,
+    turn: u64,
+    waiting: bool,
+}
+
+impl TurnScheduler {
+    /// Actors that may act this frame, most energetic first.
+    pub fn ready(&self) -> &[Entity] {
+        &self.ready
+    }
+
+    pub fn is_ready(&self, entity: Entity) -> bool {
+        self.ready.contains(&entity)
+    }
+
+    /// How many turns have gone by since the game started.
+    pub fn turn(&self) -> u64 {
+        self.turn
+    }
+
+    /// Whether the world is stopped until the player does something.
+    pub fn waiting_for_player(&self) -> bool {
+        self.waiting
+    }
+}
+
+/// Advances game time. As long as the player is able to act, time stands still
+/// until they do; otherwise everyone gains energy once per frame until the
+/// player can act again. Rendering doesn't care about any of this, and keeps
+/// going at frame rate either way.
+pub struct TurnSystem;
+
+impl<'a> System<'a> for TurnSystem {
+    type SystemData = (
+        Write<'a, TurnScheduler>,
+        Read<'a, ActionQueue>,
+        Entities<'a>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Speed>,
+        WriteStorage<'a, Energy>,
+    );
+
+    fn run(&mut self, (mut scheduler, queue, entities, players, speeds, mut energies): Self::SystemData) {
+        scheduler.ready.clear();
+
+        let player_ready = (&players, &energies).join().any(|(_, energy)| energy.0 >= ACTION_COST);
+        scheduler.waiting = player_ready && queue.is_empty();
+        if scheduler.waiting {
+            return;
+        }
+
+        if !player_ready {
+            for (energy, speed) in (&mut energies, &speeds).join() {
+                energy.0 += speed.0;
+            }
+            scheduler.turn += 1;
+        }
+
+        let mut ready = (&*entities, &energies).join()
+            .filter(|&(_, energy)| energy.0 >= ACTION_COST)
+            .map(|(entity, &energy)| (energy, entity))
+            .collect::<Vec<_>>();
+        // Highest energy goes first, and entity order breaks ties so that the
+        // order is always the same.
+        ready.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.id().cmp(&b.1.id())));
+        scheduler.ready.extend(ready.into_iter().map(|(_, entity)| entity));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input::Action;
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Player>();
+        world.register::<Speed>();
+        world.register::<Energy>();
+        world.add_resource(TurnScheduler::default());
+        world.add_resource(ActionQueue::default());
+        world
+    }
+
+    fn energy(world: &World, entity: Entity) -> i32 {
+        world.read_storage::<Energy>().get(entity).unwrap().0
+    }
+
+    #[test]
+    fn energy_builds_up_by_speed() {
+        let mut world = world();
+        let player = world.create_entity().with(Player).with(Speed(50)).with(Energy(0)).build();
+        let fast = world.create_entity().with(Speed(70)).with(Energy(0)).build();
+
+        TurnSystem.run_now(&world.res);
+        assert_eq!(energy(&world, player), 50);
+        assert_eq!(energy(&world, fast), 70);
+        assert_eq!(world.read_resource::<TurnScheduler>().turn(), 1);
+        assert!(world.read_resource::<TurnScheduler>().ready().is_empty());
+
+        TurnSystem.run_now(&world.res);
+        assert_eq!(energy(&world, player), 100);
+        assert_eq!(energy(&world, fast), 140);
+        assert_eq!(world.read_resource::<TurnScheduler>().turn(), 2);
+        assert_eq!(world.read_resource::<TurnScheduler>().ready(), &[fast, player]);
+    }
+
+    #[test]
+    fn world_waits_for_the_player() {
+        let mut world = world();
+        let player = world.create_entity().with(Player).with(Speed::default()).with(Energy(ACTION_COST)).build();
+        let other = world.create_entity().with(Speed::default()).with(Energy(0)).build();
+
+        for _ in 0..3 {
+            TurnSystem.run_now(&world.res);
+            let scheduler = world.read_resource::<TurnScheduler>();
+            assert!(scheduler.waiting_for_player());
+            assert!(scheduler.ready().is_empty());
+            assert_eq!(scheduler.turn(), 0);
+        }
+        assert_eq!(energy(&world, player), ACTION_COST);
+        assert_eq!(energy(&world, other), 0);
+
+        // Once there's something to do the player may act, but time still
+        // doesn't pass until they've spent their energy
+        world.write_resource::<ActionQueue>().push(Action::Wait);
+        TurnSystem.run_now(&world.res);
+        let scheduler = world.read_resource::<TurnScheduler>();
+        assert!(!scheduler.waiting_for_player());
+        assert_eq!(scheduler.ready(), &[player]);
+        assert_eq!(scheduler.turn(), 0);
+        assert_eq!(energy(&world, other), 0);
+    }
+
+    #[test]
+    fn ready_order_is_energy_then_entity() {
+        let mut world = world();
+        world.create_entity().with(Player).with(Speed(0)).with(Energy(0)).build();
+        let a = world.create_entity().with(Speed(0)).with(Energy(150)).build();
+        let b = world.create_entity().with(Speed(0)).with(Energy(300)).build();
+        let c = world.create_entity().with(Speed(0)).with(Energy(150)).build();
+        world.create_entity().with(Speed(0)).with(Energy(50)).build();
+
+        TurnSystem.run_now(&world.res);
+        assert_eq!(world.read_resource::<TurnScheduler>().ready(), &[b, a, c]);
+    }
+}